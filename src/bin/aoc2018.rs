@@ -0,0 +1,280 @@
+use advent_of_code_2018::*;
+use advent_of_code_2018::parse::ParseError;
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage:
+  aoc2018 run --day <N> [--part <1|2>] [--input <path|->]
+  aoc2018 reduce [--input <path|->] [--rules <path>] [--progress]
+  aoc2018 render [--input <path|->] [--format <text|ppm|png>] [--scale <N>] [--threshold <T>] [--output <path>]
+  aoc2018 list
+
+Runs a solution without cargo-aoc. The input defaults to input/2018/day<N>.txt, and `-` reads
+it from stdin. Both parts are run if --part is left out.
+
+`reduce` streams a day 5 polymer through the reducer without loading it all into memory and
+prints its reduced length, optionally using a rules file instead of the puzzle's case rule.
+
+`render` draws the day 6 partition, and the safe region if given a --threshold, to stdout or
+--output. Images use --scale pixels per cell, 4 by default.";
+
+struct Answer {
+  value: String,
+  generator: Duration,
+  runner: Duration
+}
+
+fn timed<T, R: Display>(input: &str, generator: fn(&str) -> Result<T, ParseError>, runner: fn(&T) -> R) -> Result<Answer, ParseError> {
+  let start_time = Instant::now();
+  let generated = generator(input)?;
+  let inter_time = Instant::now();
+  let value = runner(&generated).to_string();
+  let final_time = Instant::now();
+
+  Ok(Answer { value, generator: inter_time - start_time, runner: final_time - inter_time })
+}
+
+/// Shows an answer that might not exist, like a day with no unique solution for its input.
+fn or_none<T: Display>(answer: Option<T>) -> String {
+  answer.map_or_else(|| "none".to_string(), |answer| answer.to_string())
+}
+
+fn raw(input: &str) -> Result<String, ParseError> {
+  Ok(input.to_string())
+}
+
+type Solver = fn(&str) -> Result<Answer, ParseError>;
+
+/// Finds the solution for one part of one day, if there is one.
+fn solver(day: u32, part: u32) -> Option<Solver> {
+  let solver: Solver = match (day, part) {
+    (1, 1) => |input| timed(input, raw, |input| day1::day1_part1(input)),
+    (1, 2) => |input| timed(input, raw, |input| day1::day1_part2(input)),
+    (2, 1) => |input| timed(input, raw, |input| day2::day2_part1(input)),
+    (2, 2) => |input| timed(input, raw, |input| day2::day2_part2(input)),
+    (3, 1) => |input| timed(input, day3::claim_parser, |claims| day3::part1(claims)),
+    (3, 2) => |input| timed(input, day3::claim_parser, |claims| or_none(day3::part2(claims))),
+    (4, 1) => |input| timed(input, day4::record_parser, |records| or_none(day4::part1(records))),
+    (4, 2) => |input| timed(input, day4::record_parser, |records| or_none(day4::part2(records))),
+    (5, 1) => |input| timed(input, day5::polymer_parser, |polymer| day5::part1(polymer)),
+    (5, 2) => |input| timed(input, day5::polymer_parser, |polymer| day5::part2(polymer)),
+    (6, 1) => |input| timed(input, day6::coordinate_parser, |coords| day6::part1(coords)),
+    (6, 2) => |input| timed(input, day6::coordinate_parser, |coords| day6::part2(coords)),
+    (7, 1) => |input| timed(input, day7::rule_parser, day7::part1),
+    (7, 2) => |input| timed(input, day7::rule_parser, day7::part2),
+    (8, 1) => |input| timed(input, day8::parse_input, day8::part1),
+    (8, 2) => |input| timed(input, day8::parse_input, day8::part2),
+    _ => return None
+  };
+
+  Some(solver)
+}
+
+fn read_input(day: u32, path: Option<&str>) -> io::Result<String> {
+  match path {
+    Some("-") => {
+      let mut input = String::new();
+      io::stdin().read_to_string(&mut input)?;
+      Ok(input)
+    },
+    Some(path) => fs::read_to_string(path),
+    None => fs::read_to_string(format!("input/2018/day{}.txt", day))
+  }
+}
+
+fn usage_error(message: &str) -> ! {
+  eprintln!("error: {}\n\n{}", message, USAGE);
+  process::exit(2)
+}
+
+fn parse_number(flag: &str, value: Option<String>) -> u32 {
+  match value.as_ref().map(|v| v.parse::<u32>()) {
+    Some(Ok(n)) => n,
+    Some(Err(_)) => usage_error(&format!("{} expects a number, got {:?}", flag, value.unwrap())),
+    None => usage_error(&format!("{} expects a value", flag))
+  }
+}
+
+fn run<I: Iterator<Item=String>>(mut args: I) {
+  let mut day = None;
+  let mut part = None;
+  let mut path = None;
+
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--day" | "-d" => day = Some(parse_number("--day", args.next())),
+      "--part" | "-p" => part = Some(parse_number("--part", args.next())),
+      "--input" | "-i" => path = Some(args.next().unwrap_or_else(|| usage_error("--input expects a path"))),
+      other => usage_error(&format!("unexpected argument {:?}", other))
+    }
+  }
+
+  let day = day.unwrap_or_else(|| usage_error("--day is required"));
+  let parts = match part {
+    Some(part) => vec![part],
+    None => vec![1, 2]
+  };
+
+  let solvers = parts.iter()
+    .map(|part| solver(day, *part)
+      .unwrap_or_else(|| usage_error(&format!("there's no solution for day {} part {}", day, part))))
+    .collect::<Vec<Solver>>();
+
+  let input = read_input(day, path.as_deref()).unwrap_or_else(|e| {
+    eprintln!("error: couldn't read the input for day {}: {}", day, e);
+    process::exit(1)
+  });
+
+  for (part, solver) in parts.into_iter().zip(solvers) {
+    match solver(&input) {
+      Ok(answer) => println!(
+        "Day {} - Part {}: {}\n\tgenerator: {:?},\n\trunner: {:?}",
+        day, part, answer.value, answer.generator, answer.runner
+      ),
+      Err(e) => {
+        eprintln!("Day {} - Part {}: FAILED while generating:\n{}", day, part, e);
+        process::exit(1)
+      }
+    }
+  }
+}
+
+fn reduce(mut args: impl Iterator<Item=String>) {
+  let mut path = None;
+  let mut rules_path = None;
+  let mut show_progress = false;
+
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--input" | "-i" => path = Some(args.next().unwrap_or_else(|| usage_error("--input expects a path"))),
+      "--rules" | "-r" => rules_path = Some(args.next().unwrap_or_else(|| usage_error("--rules expects a path"))),
+      "--progress" => show_progress = true,
+      other => usage_error(&format!("unexpected argument {:?}", other))
+    }
+  }
+
+  fn fail(message: String) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1)
+  }
+
+  fn stream<R: day5::ReactionRules>(rules: &R, path: Option<&str>, show_progress: bool) -> usize {
+    let mut reducer = day5::StreamingReducer::new(rules);
+    let mut last_report = Instant::now();
+
+    let progress = |r: &day5::StreamingReducer<R>| {
+      if show_progress && last_report.elapsed() >= Duration::from_secs(1) {
+        eprintln!("read {} units, {} left after reacting", r.consumed(), r.len());
+        last_report = Instant::now();
+      }
+    };
+
+    let result = match path {
+      None | Some("-") => reducer.read_from(io::stdin().lock(), progress),
+      Some(path) => fs::File::open(path).and_then(|file| reducer.read_from(file, progress))
+    };
+
+    result.unwrap_or_else(|e| fail(format!("couldn't read the polymer: {}", e)));
+    reducer.len()
+  }
+
+  let length = match rules_path {
+    Some(rules_path) => {
+      let text = fs::read_to_string(&rules_path)
+        .unwrap_or_else(|e| fail(format!("couldn't read {}: {}", rules_path, e)));
+      let rules = day5::RuleTable::parse(&text)
+        .unwrap_or_else(|e| fail(format!("couldn't parse {}:\n{}", rules_path, e)));
+
+      stream(&rules, path.as_deref(), show_progress)
+    },
+    None => stream(&day5::CaseRule, path.as_deref(), show_progress)
+  };
+
+  println!("{}", length);
+}
+
+fn render(mut args: impl Iterator<Item=String>) {
+  let mut path = None;
+  let mut format = "text".to_string();
+  let mut scale = 4;
+  let mut threshold = None;
+  let mut output = None;
+
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--input" | "-i" => path = Some(args.next().unwrap_or_else(|| usage_error("--input expects a path"))),
+      "--format" | "-f" => format = args.next().unwrap_or_else(|| usage_error("--format expects text, ppm or png")),
+      "--scale" | "-s" => scale = parse_number("--scale", args.next()) as usize,
+      "--threshold" | "-t" => threshold = Some(parse_number("--threshold", args.next()) as i64),
+      "--output" | "-o" => output = Some(args.next().unwrap_or_else(|| usage_error("--output expects a path"))),
+      other => usage_error(&format!("unexpected argument {:?}", other))
+    }
+  }
+
+  if scale == 0 {
+    usage_error("--scale must be at least 1");
+  }
+
+  let input = read_input(6, path.as_deref()).unwrap_or_else(|e| {
+    eprintln!("error: couldn't read the input for day 6: {}", e);
+    process::exit(1)
+  });
+
+  let coords = day6::coordinate_parser(&input).unwrap_or_else(|e| {
+    eprintln!("error: couldn't parse the coordinates:\n{}", e);
+    process::exit(1)
+  });
+
+  let picture = day6::picture(&day6::Manhattan, &coords, threshold).unwrap_or_else(|| {
+    eprintln!("error: there aren't any coordinates to draw");
+    process::exit(1)
+  });
+
+  let bytes = match format.as_str() {
+    "text" => day6::render_text(&coords, &picture).into_bytes(),
+    "ppm" => day6::render_image(&coords, &picture, scale).to_ppm(),
+    "png" => day6::render_image(&coords, &picture, scale).to_png(),
+    other => usage_error(&format!("unknown format {:?}, expected text, ppm or png", other))
+  };
+
+  let result = match output {
+    Some(ref output) => fs::write(output, bytes),
+    None => io::stdout().write_all(&bytes)
+  };
+
+  result.unwrap_or_else(|e| {
+    eprintln!("error: couldn't write the rendering: {}", e);
+    process::exit(1)
+  });
+}
+
+fn list() {
+  for day in 1..=25 {
+    let parts = (1..=2)
+      .filter(|part| solver(day, *part).is_some())
+      .map(|part| part.to_string())
+      .collect::<Vec<String>>();
+
+    if !parts.is_empty() {
+      println!("day {}: part {}", day, parts.join(", "));
+    }
+  }
+}
+
+fn main() {
+  let mut args = env::args().skip(1);
+
+  match args.next().as_deref() {
+    Some("run") => run(args),
+    Some("reduce") => reduce(args),
+    Some("render") => render(args),
+    Some("list") => list(),
+    Some("help") | Some("--help") | Some("-h") => println!("{}", USAGE),
+    Some(other) => usage_error(&format!("unknown command {:?}", other)),
+    None => usage_error("no command given")
+  }
+}
@@ -69,5 +69,5 @@ pub fn day2_part2(input: &str) -> String {
     }
   }
 
-  return best_common;
+  best_common
 }
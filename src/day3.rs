@@ -3,6 +3,7 @@ use std::prelude::v1::{Vec};
 use regex::Regex;
 use std::collections::HashSet;
 use std::collections::HashMap;
+use crate::parse::{ParseError, parse_match};
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct Claim {
@@ -14,112 +15,508 @@ pub struct Claim {
 }
 
 #[aoc_generator(day3)]
-pub fn claim_parser(input: &str) -> Vec<Claim> {
+pub fn claim_parser(input: &str) -> Result<Vec<Claim>, ParseError> {
   lazy_static! {
-      static ref RE: Regex = Regex::new(r"#(\d*) @ (\d*),(\d*): (\d*)x(\d*)$").unwrap();
+      static ref RE: Regex = Regex::new(r"^#(\S*) @ (\S*),(\S*): (\S*)x(\S*)$").unwrap();
   }
 
   let mut claims = Vec::new();
 
-  for line in input.lines() {
-    let captures = RE.captures(line).unwrap();
+  for (index, line) in input.lines().enumerate() {
+    let captures = RE.captures(line)
+      .ok_or_else(|| ParseError::line(index, line, "a claim like \"#<id> @ <x>,<y>: <w>x<h>\""))?;
 
-    let id = captures.get(1).unwrap().as_str().parse::<usize>().unwrap();
-    let x = captures.get(2).unwrap().as_str().parse::<usize>().unwrap();
-    let y = captures.get(3).unwrap().as_str().parse::<usize>().unwrap();
-    let w = captures.get(4).unwrap().as_str().parse::<usize>().unwrap();
-    let h = captures.get(5).unwrap().as_str().parse::<usize>().unwrap();
+    let field = |n| parse_match::<usize>(index, line, captures.get(n).unwrap(), "an unsigned integer");
+
+    let id = field(1)?;
+    let x = field(2)?;
+    let y = field(3)?;
+    let w = field(4)?;
+    let h = field(5)?;
 
     claims.push(Claim { id, x, y, w, h });
   }
 
-  claims
+  Ok(claims)
 }
 
-#[aoc(day3, part1)]
-pub fn part1(claims: &Vec<Claim>) -> usize {
-  let mut occupied = HashSet::new();
-  let mut duplicated = HashSet::new();
+/// A segment tree over the gaps between sorted boundaries, tracking how much of its length is
+/// covered by at least one and at least two intervals. Intervals are only ever removed after being
+/// added, so counts don't need pushing down to children.
+struct CoverTree {
+  bounds: Vec<usize>,
+  count: Vec<isize>,
+  once: Vec<usize>,
+  twice: Vec<usize>
+}
+
+impl CoverTree {
+  fn new(bounds: Vec<usize>) -> CoverTree {
+    let size = 4 * bounds.len().max(1);
+    CoverTree { bounds, count: vec![0; size], once: vec![0; size], twice: vec![0; size] }
+  }
+
+  fn gaps(&self) -> usize {
+    self.bounds.len().saturating_sub(1)
+  }
+
+  /// Adds `delta` to the count of every gap from `from` up to, but not including, `to`.
+  fn add(&mut self, from: usize, to: usize, delta: isize) {
+    if from < to {
+      self.update(1, 0, self.gaps(), from, to, delta);
+    }
+  }
+
+  fn update(&mut self, node: usize, start: usize, end: usize, from: usize, to: usize, delta: isize) {
+    if to <= start || end <= from {
+      return;
+    }
+
+    if from <= start && end <= to {
+      self.count[node] += delta;
+    } else {
+      let middle = (start + end) / 2;
+      self.update(2 * node, start, middle, from, to, delta);
+      self.update(2 * node + 1, middle, end, from, to, delta);
+    }
+
+    let length = self.bounds[end] - self.bounds[start];
+    let leaf = end - start == 1;
+    let (children_once, children_twice) = if leaf {
+      (0, 0)
+    } else {
+      (self.once[2 * node] + self.once[2 * node + 1], self.twice[2 * node] + self.twice[2 * node + 1])
+    };
+
+    self.once[node] = if self.count[node] >= 1 { length } else { children_once };
+    self.twice[node] = match self.count[node] {
+      0 => children_twice,
+      1 => children_once,
+      _ => length
+    };
+  }
+
+  /// How much length is covered by at least two intervals.
+  fn covered_twice(&self) -> usize {
+    if self.gaps() == 0 { 0 } else { self.twice[1] }
+  }
+
+  /// How much of the length from gap `from` up to gap `to` is covered by at least one interval.
+  fn covered_within(&self, from: usize, to: usize) -> usize {
+    if from < to { self.covered_node(1, 0, self.gaps(), from, to) } else { 0 }
+  }
+
+  fn covered_node(&self, node: usize, start: usize, end: usize, from: usize, to: usize) -> usize {
+    if to <= start || end <= from {
+      return 0;
+    }
+
+    if self.count[node] >= 1 {
+      return self.bounds[end.min(to)] - self.bounds[start.max(from)];
+    }
+
+    if from <= start && end <= to {
+      return self.once[node];
+    }
 
+    let middle = (start + end) / 2;
+    self.covered_node(2 * node, start, middle, from, to) + self.covered_node(2 * node + 1, middle, end, from, to)
+  }
+}
+
+/// A segment tree over the same gaps as `CoverTree`, where ranges get painted with a number and we
+/// can ask for the biggest number painted anywhere in a range.
+struct PaintTree {
+  /// The biggest number painted over the whole of a node's range.
+  whole: Vec<Option<usize>>,
+  /// The biggest number painted anywhere in a node's range.
+  any: Vec<Option<usize>>,
+  gaps: usize
+}
+
+impl PaintTree {
+  fn new(gaps: usize) -> PaintTree {
+    let size = 4 * gaps.max(1);
+    PaintTree { whole: vec![None; size], any: vec![None; size], gaps }
+  }
+
+  fn paint(&mut self, from: usize, to: usize, value: usize) {
+    if from < to {
+      self.paint_node(1, 0, self.gaps, from, to, value);
+    }
+  }
+
+  fn paint_node(&mut self, node: usize, start: usize, end: usize, from: usize, to: usize, value: usize) {
+    if to <= start || end <= from {
+      return;
+    }
+
+    self.any[node] = self.any[node].max(Some(value));
+
+    if from <= start && end <= to {
+      self.whole[node] = self.whole[node].max(Some(value));
+    } else {
+      let middle = (start + end) / 2;
+      self.paint_node(2 * node, start, middle, from, to, value);
+      self.paint_node(2 * node + 1, middle, end, from, to, value);
+    }
+  }
+
+  fn max(&self, from: usize, to: usize) -> Option<usize> {
+    if from < to { self.max_node(1, 0, self.gaps, from, to) } else { None }
+  }
+
+  fn max_node(&self, node: usize, start: usize, end: usize, from: usize, to: usize) -> Option<usize> {
+    if to <= start || end <= from {
+      return None;
+    }
+
+    if from <= start && end <= to {
+      return self.any[node];
+    }
+
+    let middle = (start + end) / 2;
+    self.whole[node]
+      .max(self.max_node(2 * node, start, middle, from, to))
+      .max(self.max_node(2 * node + 1, middle, end, from, to))
+  }
+}
+
+/// What a sweep across the fabric found.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Overlaps {
+  /// How many square inches are inside two or more claims.
+  pub area: usize,
+  /// The IDs of the claims that don't overlap any other, in the order they were given.
+  pub intact: Vec<usize>
+}
+
+/// Sweeps a line across the fabric from left to right, stopping wherever a claim starts or ends.
+/// Between stops, the area inside two or more claims is the width since the last stop times the
+/// length of the line inside two or more claims, which a segment tree over the claims' top and
+/// bottom edges keeps track of.
+///
+/// A claim overlaps another if, when it starts, the line is already inside some other claim
+/// alongside it, or if by the time it ends another claim has started alongside it. The second is
+/// checked by painting each claim's edges with the stop it started at. That makes the whole sweep
+/// take time proportional to the number of claims times its logarithm, whatever the fabric's size.
+pub fn sweep(claims: &[Claim]) -> Overlaps {
+  let mut ys = Vec::with_capacity(2 * claims.len());
   for claim in claims {
-    for x in claim.x..claim.x + claim.w {
-      for y in claim.y..claim.y + claim.h {
-        let coord = (x,y);
+    ys.push(claim.y);
+    ys.push(claim.y + claim.h);
+  }
+  ys.sort();
+  ys.dedup();
+  let ranges = claims.iter()
+    .map(|claim| (ys.binary_search(&claim.y).unwrap(), ys.binary_search(&(claim.y + claim.h)).unwrap()))
+    .collect::<Vec<(usize, usize)>>();
 
-        if occupied.contains(&coord) {
-          duplicated.insert(coord);
-        } else {
-          occupied.insert(coord);
-        }
+  // ends sort before starts at the same x, since claims that only touch don't overlap
+  let mut stops = Vec::new();
+  // empty claims can't overlap anything, so they don't need stops
+  for (index, claim) in claims.iter().enumerate().filter(|(_, claim)| claim.w > 0 && claim.h > 0) {
+    stops.push((claim.x, 1, index));
+    stops.push((claim.x + claim.w, 0, index));
+  }
+  stops.sort();
+
+  let mut cover = CoverTree::new(ys.clone());
+  let mut painted = PaintTree::new(cover.gaps());
+  let mut started = vec![0; claims.len()];
+  let mut overlapping = HashSet::new();
+
+  let mut area = 0;
+  let mut last_x = stops.first().map_or(0, |stop| stop.0);
+
+  for (time, &(x, kind, index)) in stops.iter().enumerate() {
+    area += cover.covered_twice() * (x - last_x);
+    last_x = x;
+
+    let claim = &claims[index];
+    let (from, to) = ranges[index];
+
+    if kind == 1 {
+      if cover.covered_within(from, to) > 0 {
+        overlapping.insert(claim.id);
       }
+
+      cover.add(from, to, 1);
+      painted.paint(from, to, time);
+      started[index] = time;
+    } else {
+      if painted.max(from, to) > Some(started[index]) {
+        overlapping.insert(claim.id);
+      }
+
+      cover.add(from, to, -1);
     }
   }
 
-  duplicated.len()
+  let intact = claims.iter()
+    .filter(|claim| !overlapping.contains(&claim.id))
+    .map(|claim| claim.id)
+    .collect();
+
+  Overlaps { area, intact }
+}
+
+#[aoc(day3, part1)]
+pub fn part1(claims: &[Claim]) -> usize {
+  sweep(claims).area
 }
 
+/// The ID of the one claim that doesn't overlap any other, or `None` if there isn't exactly one.
+/// `Fabric::intact` lists them all.
 #[aoc(day3, part2)]
-pub fn part2(claims: &Vec<Claim>) -> usize {
-  let mut occupied_sets = HashMap::new();
+pub fn part2(claims: &[Claim]) -> Option<usize> {
+  match sweep(claims).intact.as_slice() {
+    [id] => Some(*id),
+    _ => None
+  }
+}
 
-  for claim in claims {
-    let mut occupied = HashSet::new();
+impl Claim {
+  pub fn id(&self) -> usize {
+    self.id
+  }
+
+  pub fn area(&self) -> usize {
+    self.w * self.h
+  }
+
+  pub fn contains(&self, x: usize, y: usize) -> bool {
+    x >= self.x && x < self.x + self.w && y >= self.y && y < self.y + self.h
+  }
 
-    for x in claim.x..claim.x + claim.w {
-      for y in claim.y..claim.y + claim.h {
-        let coord = (x,y);
-        occupied.insert(coord);
+  /// How many square inches this claim shares with another.
+  pub fn overlap_area(&self, other: &Claim) -> usize {
+    let w = (self.x + self.w).min(other.x + other.w).saturating_sub(self.x.max(other.x));
+    let h = (self.y + self.h).min(other.y + other.h).saturating_sub(self.y.max(other.y));
+    w * h
+  }
+}
+
+/// A rectangle of square inches, with its top left corner at `x`, `y`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Rect {
+  pub x: usize,
+  pub y: usize,
+  pub w: usize,
+  pub h: usize
+}
+
+impl Rect {
+  pub fn area(&self) -> usize {
+    self.w * self.h
+  }
+
+  /// Every square inch in the rectangle, row by row.
+  pub fn cells(&self) -> impl Iterator<Item=(usize, usize)> {
+    let rect = *self;
+    (rect.y..rect.y + rect.h).flat_map(move |y| (rect.x..rect.x + rect.w).map(move |x| (x, y)))
+  }
+}
+
+/// A set of claims, indexed so we can ask questions about how they overlap. Claims are looked up
+/// by ID, and IDs are expected to be unique.
+pub struct Fabric {
+  claims: Vec<Claim>,
+  positions: HashMap<usize, usize>,
+  /// The positions of the claims, sorted by their left edges.
+  by_left: Vec<usize>,
+  /// For each claim, the positions of the claims overlapping it, in order.
+  overlaps: Vec<Vec<usize>>
+}
+
+impl Fabric {
+  /// Indexes some claims. Finding which claims overlap takes time proportional to the number of
+  /// claims times its logarithm, plus the number of pairs of claims that are side by side.
+  pub fn new(claims: Vec<Claim>) -> Fabric {
+    let positions = claims.iter().enumerate().map(|(position, claim)| (claim.id, position)).collect();
+
+    let mut by_left = (0..claims.len()).collect::<Vec<usize>>();
+    by_left.sort_by_key(|position| claims[*position].x);
+
+    let mut overlaps = vec![Vec::new(); claims.len()];
+    for (i, &first) in by_left.iter().enumerate() {
+      let claim = &claims[first];
+
+      for &second in by_left[i + 1..].iter().take_while(|second| claims[**second].x < claim.x + claim.w) {
+        if claim.overlap_area(&claims[second]) > 0 {
+          overlaps[first].push(second);
+          overlaps[second].push(first);
+        }
       }
     }
 
-    occupied_sets.insert(claim.id, occupied);
+    for neighbours in overlaps.iter_mut() {
+      neighbours.sort();
+    }
+
+    Fabric { claims, positions, by_left, overlaps }
   }
 
-  let mut nonoverlapping = Vec::new();
+  pub fn claims(&self) -> &[Claim] {
+    &self.claims
+  }
 
-  for first in claims {
-    let first_occupied = occupied_sets.get(&first.id).unwrap();
-    let mut overlaps = false;
+  pub fn claim(&self, id: usize) -> Option<&Claim> {
+    self.positions.get(&id).map(|position| &self.claims[*position])
+  }
 
-    for second in claims {
-      if first == second {
-        continue;
+  fn ids(&self, positions: &[usize]) -> Vec<usize> {
+    positions.iter().map(|position| self.claims[*position].id).collect()
+  }
+
+  /// The IDs of the claims overlapping the claim with this ID, in the order they were given. It's
+  /// empty if there's no such claim.
+  pub fn overlapping(&self, id: usize) -> Vec<usize> {
+    self.positions.get(&id).map_or_else(Vec::new, |position| self.ids(&self.overlaps[*position]))
+  }
+
+  /// The IDs of the claims that don't overlap any other, in the order they were given.
+  pub fn intact(&self) -> Vec<usize> {
+    (0..self.claims.len())
+      .filter(|position| self.overlaps[*position].is_empty())
+      .map(|position| self.claims[position].id)
+      .collect()
+  }
+
+  /// How many square inches two claims share, or `None` if either ID isn't a claim.
+  pub fn overlap_area(&self, first: usize, second: usize) -> Option<usize> {
+    Some(self.claim(first)?.overlap_area(self.claim(second)?))
+  }
+
+  /// How many claims include this square inch.
+  pub fn coverage(&self, x: usize, y: usize) -> usize {
+    let started = self.by_left.partition_point(|position| self.claims[*position].x <= x);
+
+    self.by_left[..started].iter()
+      .filter(|position| self.claims[**position].contains(x, y))
+      .count()
+  }
+
+  /// The square inches covered by at least `k` claims, as rectangles that don't overlap each other.
+  /// The fabric goes on forever, so a `k` of 0 is treated like 1.
+  ///
+  /// The rectangles come from the grid made by every claim's edges: each column of that grid is
+  /// split into runs of cells with enough coverage, which makes this take time proportional to the
+  /// number of claims squared, however big they are.
+  pub fn covered_at_least(&self, k: usize) -> Vec<Rect> {
+    let k = k.max(1);
+    let claims = self.claims.iter().filter(|claim| claim.area() > 0).collect::<Vec<&Claim>>();
+
+    let mut xs = Vec::new();
+    let mut ys = Vec::new();
+    for claim in claims.iter() {
+      xs.push(claim.x);
+      xs.push(claim.x + claim.w);
+      ys.push(claim.y);
+      ys.push(claim.y + claim.h);
+    }
+
+    xs.sort();
+    xs.dedup();
+    ys.sort();
+    ys.dedup();
+
+    let mut changes = vec![Vec::new(); xs.len()];
+    for claim in claims.iter() {
+      let from = ys.binary_search(&claim.y).unwrap();
+      let to = ys.binary_search(&(claim.y + claim.h)).unwrap();
+
+      changes[xs.binary_search(&claim.x).unwrap()].push((from, to, 1));
+      changes[xs.binary_search(&(claim.x + claim.w)).unwrap()].push((from, to, -1));
+    }
+
+    let mut coverage = vec![0isize; ys.len()];
+    let mut rects = Vec::new();
+
+    for column in 0..xs.len().saturating_sub(1) {
+      for &(from, to, delta) in changes[column].iter() {
+        for count in coverage[from..to].iter_mut() {
+          *count += delta;
+        }
       }
 
-      let second_occupied = occupied_sets.get(&second.id).unwrap();
+      let mut run_start = None;
+      for row in 0..ys.len() {
+        let covered = row + 1 < ys.len() && coverage[row] >= k as isize;
 
-      if !first_occupied.is_disjoint(second_occupied) {
-        overlaps = true;
-        break;
+        match (run_start, covered) {
+          (None, true) => run_start = Some(row),
+          (Some(start), false) => {
+            rects.push(Rect { x: xs[column], y: ys[start], w: xs[column + 1] - xs[column], h: ys[row] - ys[start] });
+            run_start = None;
+          },
+          _ => {}
+        }
       }
     }
 
-    if !overlaps {
-      nonoverlapping.push(first.id);
-    }
+    rects
   }
 
-  assert_eq!(1, nonoverlapping.len());
-  *nonoverlapping.first().unwrap()
+  /// The IDs of the claims in each group of claims linked by overlaps, with every claim in exactly
+  /// one group. Groups are in the order of their first claim, and the claims in each group are in
+  /// the order they were given.
+  pub fn components(&self) -> Vec<Vec<usize>> {
+    let mut seen = vec![false; self.claims.len()];
+    let mut components = Vec::new();
+
+    for start in 0..self.claims.len() {
+      if seen[start] {
+        continue;
+      }
+
+      seen[start] = true;
+      let mut component = vec![start];
+      let mut stack = vec![start];
+
+      while let Some(position) = stack.pop() {
+        for &neighbour in self.overlaps[position].iter() {
+          if !seen[neighbour] {
+            seen[neighbour] = true;
+            component.push(neighbour);
+            stack.push(neighbour);
+          }
+        }
+      }
+
+      component.sort();
+      components.push(self.ids(&component));
+    }
+
+    components
+  }
 }
 
 #[cfg(test)]
 mod tests {
-  use crate::day3::{
-    Claim,
-    claim_parser,
-    part1,
-    part2
-  };
+  use crate::day3::*;
+  use crate::testing::Lcg;
 
   #[test]
   pub fn claim_parser_test() {
-    assert_eq!([Claim { id: 1, x: 1, y: 3, w: 4, h: 4 }], claim_parser("#1 @ 1,3: 4x4").as_ref());
-    assert_eq!([Claim { id: 2, x: 3, y: 1, w: 4, h: 4 }], claim_parser("#2 @ 3,1: 4x4").as_ref());
-    assert_eq!([Claim { id: 3, x: 5, y: 5, w: 2, h: 2 }], claim_parser("#3 @ 5,5: 2x2").as_ref());
+    assert_eq!([Claim { id: 1, x: 1, y: 3, w: 4, h: 4 }], claim_parser("#1 @ 1,3: 4x4").unwrap().as_ref());
+    assert_eq!([Claim { id: 2, x: 3, y: 1, w: 4, h: 4 }], claim_parser("#2 @ 3,1: 4x4").unwrap().as_ref());
+    assert_eq!([Claim { id: 3, x: 5, y: 5, w: 2, h: 2 }], claim_parser("#3 @ 5,5: 2x2").unwrap().as_ref());
+
+    assert_eq!([Claim { id: 1, x: 287, y: 428, w: 27, h: 20 }], claim_parser("#1 @ 287,428: 27x20").unwrap().as_ref());
+    assert_eq!([Claim { id: 1397, x: 888, y: 761, w: 25, h: 24 }], claim_parser("#1397 @ 888,761: 25x24").unwrap().as_ref());
+  }
+
+  #[test]
+  pub fn claim_parser_error_test() {
+    let error = claim_parser("#1 @ 1,3: 4x4\n#2 @ 3,y: 4x4").unwrap_err();
+    assert_eq!((2, 8, "y"), (error.line, error.column, error.text.as_str()));
+
+    let error = claim_parser("#1 @ 1,3: 4x4\n\n#3 @ 5,5: 2x2").unwrap_err();
+    assert_eq!((2, 1, ""), (error.line, error.column, error.text.as_str()));
 
-    assert_eq!([Claim { id: 1, x: 287, y: 428, w: 27, h: 20 }], claim_parser("#1 @ 287,428: 27x20").as_ref());
-    assert_eq!([Claim { id: 1397, x: 888, y: 761, w: 25, h: 24 }], claim_parser("#1397 @ 888,761: 25x24").as_ref());
+    let error = claim_parser("#3 5,5: 2x2").unwrap_err();
+    assert_eq!((1, 1, "#3 5,5: 2x2"), (error.line, error.column, error.text.as_str()));
   }
 
   #[test]
@@ -128,16 +525,211 @@ mod tests {
       Claim { id: 1, x: 1, y: 3, w: 4, h: 4 },
       Claim { id: 2, x: 3, y: 1, w: 4, h: 4 },
       Claim { id: 3, x: 5, y: 5, w: 2, h: 2 }
-    ].iter().cloned().collect::<Vec<Claim>>()));
+    ]));
   }
 
   #[test]
   pub fn part2_test() {
-    assert_eq!(3, part2(&[
+    assert_eq!(Some(3), part2(&[
       Claim { id: 1, x: 1, y: 3, w: 4, h: 4 },
       Claim { id: 2, x: 3, y: 1, w: 4, h: 4 },
       Claim { id: 3, x: 5, y: 5, w: 2, h: 2 }
-    ].iter().cloned().collect::<Vec<Claim>>()));
+    ]));
+
+    assert_eq!(None, part2(&[
+      Claim { id: 1, x: 1, y: 3, w: 4, h: 4 },
+      Claim { id: 2, x: 3, y: 1, w: 4, h: 4 }
+    ]));
+    assert_eq!(None, part2(&[
+      Claim { id: 1, x: 0, y: 0, w: 2, h: 2 },
+      Claim { id: 2, x: 3, y: 3, w: 2, h: 2 }
+    ]));
+  }
+
+  fn random_claims(seed: u64, count: usize, size: u64, max_side: u64) -> Vec<Claim> {
+    let mut rng = Lcg::new(seed);
+    let mut next = |modulus: u64| rng.below(modulus) as usize;
+
+    (1..=count)
+      .map(|id| Claim { id, x: next(size), y: next(size), w: next(max_side + 1), h: next(max_side + 1) })
+      .collect()
+  }
+
+  /// The original way of finding the overlapping area, one square inch at a time. Tests check the
+  /// sweep against it.
+  fn overlap_area_brute_force(claims: &[Claim]) -> usize {
+    let mut occupied = HashSet::new();
+    let mut duplicated = HashSet::new();
+
+    for claim in claims {
+      for x in claim.x..claim.x + claim.w {
+        for y in claim.y..claim.y + claim.h {
+          let coord = (x,y);
+
+          if occupied.contains(&coord) {
+            duplicated.insert(coord);
+          } else {
+            occupied.insert(coord);
+          }
+        }
+      }
+    }
+
+    duplicated.len()
   }
 
+  /// The original way of finding claims that don't overlap any other, comparing every claim's
+  /// square inches with every other's.
+  fn intact_claims_brute_force(claims: &[Claim]) -> Vec<usize> {
+    let mut occupied_sets = HashMap::new();
+
+    for claim in claims {
+      let mut occupied = HashSet::new();
+
+      for x in claim.x..claim.x + claim.w {
+        for y in claim.y..claim.y + claim.h {
+          let coord = (x,y);
+          occupied.insert(coord);
+        }
+      }
+
+      occupied_sets.insert(claim.id, occupied);
+    }
+
+    let mut nonoverlapping = Vec::new();
+
+    for first in claims {
+      let first_occupied = occupied_sets.get(&first.id).unwrap();
+      let mut overlaps = false;
+
+      for second in claims {
+        if first == second {
+          continue;
+        }
+
+        let second_occupied = occupied_sets.get(&second.id).unwrap();
+
+        if !first_occupied.is_disjoint(second_occupied) {
+          overlaps = true;
+          break;
+        }
+      }
+
+      if !overlaps {
+        nonoverlapping.push(first.id);
+      }
+    }
+
+    nonoverlapping
+  }
+
+  #[test]
+  pub fn sweep_test() {
+    let claims = claim_parser("#1 @ 1,3: 4x4\n#2 @ 3,1: 4x4\n#3 @ 5,5: 2x2").unwrap();
+    assert_eq!(Overlaps { area: 4, intact: vec![3] }, sweep(&claims));
+
+    // claims that only touch don't overlap, and empty claims never do
+    let claims = claim_parser("#1 @ 0,0: 2x2\n#2 @ 2,0: 2x2\n#3 @ 0,2: 4x1\n#4 @ 1,1: 0x5").unwrap();
+    assert_eq!(Overlaps { area: 0, intact: vec![1, 2, 3, 4] }, sweep(&claims));
+
+    let claims = claim_parser("#1 @ 0,0: 3x3\n#2 @ 1,1: 1x1\n#3 @ 0,0: 3x3").unwrap();
+    assert_eq!(Overlaps { area: 9, intact: vec![] }, sweep(&claims));
+
+    assert_eq!(Overlaps { area: 0, intact: vec![] }, sweep(&[]));
+  }
+
+  #[test]
+  pub fn sweep_matches_brute_force_test() {
+    for seed in 0..20 {
+      let claims = random_claims(seed, 40, 60, 15);
+
+      assert_eq!(overlap_area_brute_force(&claims), sweep(&claims).area, "seed {}", seed);
+      assert_eq!(intact_claims_brute_force(&claims), sweep(&claims).intact, "seed {}", seed);
+    }
+  }
+
+  #[test]
+  pub fn sweep_huge_fabric_test() {
+    let claims = claim_parser("#1 @ 0,0: 1000000000x1000000000
+#2 @ 999999999,999999999: 1000000000x1000000000
+#3 @ 5000000000,0: 1x1").unwrap();
+
+    assert_eq!(Overlaps { area: 1, intact: vec![3] }, sweep(&claims));
+  }
+
+  #[test]
+  pub fn fabric_test() {
+    let fabric = Fabric::new(claim_parser("#1 @ 1,3: 4x4
+#2 @ 3,1: 4x4
+#3 @ 5,5: 2x2
+#4 @ 4,4: 2x2
+#5 @ 20,20: 3x3").unwrap());
+
+    assert_eq!(vec![2, 4], fabric.overlapping(1));
+    assert_eq!(vec![1, 4], fabric.overlapping(2));
+    assert_eq!(vec![1, 2, 3], fabric.overlapping(4));
+    assert_eq!(Vec::<usize>::new(), fabric.overlapping(5));
+    assert_eq!(Vec::<usize>::new(), fabric.overlapping(99));
+    assert_eq!(vec![5], fabric.intact());
+
+    assert_eq!(Some(4), fabric.overlap_area(1, 2));
+    assert_eq!(Some(0), fabric.overlap_area(1, 3));
+    assert_eq!(Some(1), fabric.overlap_area(3, 4));
+    assert_eq!(None, fabric.overlap_area(1, 99));
+
+    assert_eq!(3, fabric.coverage(4, 4));
+    assert_eq!(1, fabric.coverage(1, 3));
+    assert_eq!(0, fabric.coverage(0, 0));
+    assert_eq!(1, fabric.coverage(22, 22));
+
+    assert_eq!(vec![Rect { x: 4, y: 4, w: 1, h: 1 }], fabric.covered_at_least(3));
+    assert_eq!(Vec::<Rect>::new(), fabric.covered_at_least(4));
+    assert_eq!(vec![vec![1, 2, 3, 4], vec![5]], fabric.components());
+  }
+
+  #[test]
+  pub fn fabric_matches_brute_force_test() {
+    for seed in 0..10 {
+      let claims = random_claims(seed, 30, 40, 12);
+      let fabric = Fabric::new(claims.clone());
+
+      for k in 0..5 {
+        let mut expected = Vec::new();
+        for y in 0..60 {
+          for x in 0..60 {
+            let coverage = claims.iter().filter(|claim| claim.contains(x, y)).count();
+            assert_eq!(coverage, fabric.coverage(x, y));
+
+            if coverage >= k.max(1) {
+              expected.push((x, y));
+            }
+          }
+        }
+
+        let mut cells = fabric.covered_at_least(k).iter().flat_map(|rect| rect.cells()).collect::<Vec<(usize, usize)>>();
+        cells.sort_by_key(|&(x, y)| (y, x));
+        assert_eq!(expected, cells, "seed {} k {}", seed, k);
+      }
+
+      let area = fabric.covered_at_least(2).iter().map(|rect| rect.area()).sum::<usize>();
+      assert_eq!(sweep(&claims).area, area);
+      assert_eq!(sweep(&claims).intact, fabric.intact());
+
+      for first in claims.iter() {
+        let expected = claims.iter()
+          .filter(|second| second.id != first.id && first.overlap_area(second) > 0)
+          .map(|second| second.id)
+          .collect::<Vec<usize>>();
+        assert_eq!(expected, fabric.overlapping(first.id));
+      }
+
+      let components = fabric.components();
+      assert_eq!(claims.len(), components.iter().map(|component| component.len()).sum::<usize>());
+      for component in components.iter() {
+        for id in component.iter() {
+          assert!(fabric.overlapping(*id).iter().all(|other| component.contains(other)));
+        }
+      }
+    }
+  }
 }
@@ -1,13 +1,13 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 use std::prelude::v1::{Vec};
-use regex::Regex;
-use std::collections::HashMap;
+use crate::parse::ParseError;
+use std::collections::{HashMap, HashSet};
 use std::cmp::Ordering;
-use std::ops::Sub;
+use std::convert::TryFrom;
+use std::ops::{Add, Sub};
 use std::fmt::{Formatter, Error, Debug};
-use std::iter::Step;
 
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
 pub struct Time {
   pub year: u16,
   pub month: u16,
@@ -55,58 +55,216 @@ impl Sub for Time {
   type Output = isize;
 
   fn sub(self, rhs: Time) -> <Self as Sub<Time>>::Output {
-    (self.year as isize - rhs.year as isize)   * 12 * 30 * 24 * 60 +
-    (self.month as isize - rhs.month as isize)      * 30 * 24 * 60 +
-    (self.day as isize   - rhs.day as isize)             * 24 * 60 +
-    (self.hour as isize  - rhs.hour as isize)                 * 60 +
-    (self.minute as isize - rhs.minute as isize)
+    (self.to_minutes() - rhs.to_minutes()) as isize
   }
 }
 
-impl Step for Time {
-  fn steps_between(start: &Self, end: &Self) -> Option<usize> {
-    let diff = *end - *start;
+/// Panics if the time lands outside the years 0 to 65535.
+impl Add<isize> for Time {
+  type Output = Time;
 
-    if diff > 0 {
-      Some(diff as usize)
+  fn add(self, minutes: isize) -> Time {
+    Time::from_minutes(self.to_minutes() + minutes as i64)
+  }
+}
+
+/// Panics if the time lands outside the years 0 to 65535.
+impl Sub<isize> for Time {
+  type Output = Time;
+
+  fn sub(self, minutes: isize) -> Time {
+    Time::from_minutes(self.to_minutes() - minutes as i64)
+  }
+}
+
+/// Leap years in the proleptic Gregorian calendar, which is what the puzzle's 1518 dates use.
+pub fn is_leap_year(year: u16) -> bool {
+  (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+// Converting between dates and a day count uses Howard Hinnant's `days_from_civil` algorithm, which
+// counts days from 1970-01-01 and works for any Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+  let year = if month <= 2 { year - 1 } else { year };
+  let era = year.div_euclid(400);
+  let year_of_era = year - era * 400;
+  let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+  let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+  era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+  let days = days + 719468;
+  let era = days.div_euclid(146097);
+  let day_of_era = days - era * 146097;
+  let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let shifted_month = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+  let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+  let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+  (year, month, day)
+}
+
+impl Time {
+  pub fn days_in_month(&self) -> u16 {
+    match self.month {
+      2 if is_leap_year(self.year) => 29,
+      2 => 28,
+      4 | 6 | 9 | 11 => 30,
+      _ => 31
+    }
+  }
+
+  /// Whether this is a real date and time, e.g. not February 30th or 24:00.
+  pub fn is_valid(&self) -> bool {
+    (1..=12).contains(&self.month) &&
+      (1..=self.days_in_month()).contains(&self.day) &&
+      self.hour < 24 &&
+      self.minute < 60
+  }
+
+  /// The number of minutes since 1970-01-01 00:00, negative for earlier times.
+  pub fn to_minutes(&self) -> i64 {
+    let days = days_from_civil(self.year as i64, self.month as i64, self.day as i64);
+
+    (days * 24 + self.hour as i64) * 60 + self.minute as i64
+  }
+
+  /// The time this many minutes since 1970-01-01 00:00, or `None` if it's outside the years 0 to
+  /// 65535, which are all a `Time` can hold.
+  pub fn checked_from_minutes(minutes: i64) -> Option<Time> {
+    let (year, month, day) = civil_from_days(minutes.div_euclid(24 * 60));
+    let minute_of_day = minutes.rem_euclid(24 * 60);
+
+    Some(Time {
+      year: u16::try_from(year).ok()?,
+      month: month as u16,
+      day: day as u16,
+      hour: (minute_of_day / 60) as u16,
+      minute: (minute_of_day % 60) as u16
+    })
+  }
+
+  /// Like `checked_from_minutes`, but panics outside the years 0 to 65535, the way integer
+  /// arithmetic does when it overflows. Adding or subtracting minutes goes through this.
+  pub fn from_minutes(minutes: i64) -> Time {
+    Time::checked_from_minutes(minutes)
+      .unwrap_or_else(|| panic!("{} minutes from 1970 is outside the years a Time can hold", minutes))
+  }
+
+  /// The time one minute later, carrying into the hour, day, month and year as needed.
+  pub fn succ(&self) -> Time {
+    let mut t = *self;
+
+    t.minute += 1;
+    if t.minute == 60 {
+      t.minute = 0;
+      t.hour += 1;
+    }
+
+    if t.hour == 24 {
+      t.hour = 0;
+      t.day += 1;
+    }
+
+    if t.day > t.days_in_month() {
+      t.day = 1;
+      t.month += 1;
+    }
+
+    if t.month > 12 {
+      t.month = 1;
+      t.year += 1;
+    }
+
+    t
+  }
+
+  /// The time one minute earlier, borrowing from the hour, day, month and year as needed.
+  pub fn pred(&self) -> Time {
+    let mut t = *self;
+
+    if t.minute > 0 {
+      t.minute -= 1;
+      return t;
+    }
+
+    t.minute = 59;
+    if t.hour > 0 {
+      t.hour -= 1;
+      return t;
+    }
+
+    t.hour = 23;
+    if t.day > 1 {
+      t.day -= 1;
+      return t;
+    }
+
+    if t.month > 1 {
+      t.month -= 1;
     } else {
-      None
+      t.month = 12;
+      t.year -= 1;
     }
+
+    t.day = t.days_in_month();
+    t
   }
 
-  fn replace_one(&mut self) -> Self {
-    unimplemented!()
+  /// Midnight at the start of this time's day.
+  pub fn date(&self) -> Time {
+    Time { hour: 0, minute: 0, ..*self }
   }
 
-  fn replace_zero(&mut self) -> Self {
-    unimplemented!()
+  /// Every minute from this time up to, but not including, `end`.
+  pub fn until(self, end: Time) -> TimeRange {
+    TimeRange { start: self, end }
   }
+}
 
-  fn add_one(&self) -> Self {
-    Time {
-      year: self.year,
-      month: self.month,
-      day: self.day,
-      hour: self.hour,
-      minute: self.minute + 1
+/// A half-open range of minutes, the stable replacement for `start..end` over `Time`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TimeRange {
+  pub start: Time,
+  pub end: Time
+}
+
+impl Iterator for TimeRange {
+  type Item = Time;
+
+  fn next(&mut self) -> Option<Time> {
+    if self.start < self.end {
+      let t = self.start;
+      self.start = t.succ();
+      Some(t)
+    } else {
+      None
     }
   }
 
-  fn sub_one(&self) -> Self {
-    unimplemented!()
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    let len = (self.end - self.start).max(0) as usize;
+    (len, Some(len))
   }
+}
 
-  fn add_usize(&self, n: usize) -> Option<Self> {
-    Some(Time {
-      year: self.year,
-      month: self.month,
-      day: self.day,
-      hour: self.hour,
-      minute: (self.minute as usize + n) as u16
-    })
+impl DoubleEndedIterator for TimeRange {
+  fn next_back(&mut self) -> Option<Time> {
+    if self.start < self.end {
+      self.end = self.end.pred();
+      Some(self.end)
+    } else {
+      None
+    }
   }
 }
 
+impl ExactSizeIterator for TimeRange {}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum RecordEvent {
   BeginShift(u64),
@@ -122,7 +280,7 @@ pub struct Record {
 
 impl PartialOrd for Record {
   fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-    self.time.partial_cmp(&other.time)
+    Some(self.cmp(other))
   }
 }
 
@@ -132,62 +290,197 @@ impl Ord for Record {
   }
 }
 
-#[aoc_generator(day4)]
-pub fn record_parser(input: &str) -> Vec<Record> {
-  lazy_static! {
-      static ref BEGIN_SHIFT_RE: Regex  = Regex::new(r"\[(\d*)-(\d*)-(\d*) (\d*):(\d*)\] Guard \#(\d*) begins shift$").unwrap();
-      static ref FALLS_ASLEEP_RE: Regex = Regex::new(r"\[(\d*)-(\d*)-(\d*) (\d*):(\d*)\] falls asleep$").unwrap();
-      static ref WAKES_UP_RE: Regex     = Regex::new(r"\[(\d*)-(\d*)-(\d*) (\d*):(\d*)\] wakes up$").unwrap();
-  }
-
-  let mut records = Vec::new();
-
-  for line in input.lines() {
-    for cap in BEGIN_SHIFT_RE.captures_iter(line) {
-      records.push(Record {
-        time: Time {
-          year: cap[1].parse::<u16>().unwrap(),
-          month: cap[2].parse::<u16>().unwrap(),
-          day: cap[3].parse::<u16>().unwrap(),
-          hour: cap[4].parse::<u16>().unwrap(),
-          minute: cap[5].parse::<u16>().unwrap(),
-        },
-        event: RecordEvent::BeginShift(cap[6].parse::<u64>().unwrap())
-      });
+const RECORD_SHAPE: &str = "a record like \"[YYYY-MM-DD hh:mm] <event>\"";
+const EVENT_SHAPE: &str = "\"Guard #<id> begins shift\", \"falls asleep\" or \"wakes up\"";
+
+/// Reads the number starting at byte `start` of `line` and ending just before `terminator`,
+/// returning it along with the offset just past the terminator.
+fn timestamp_field(index: usize, line: &str, start: usize, terminator: char) -> Result<(u16, usize), ParseError> {
+  let length = line.get(start..)
+    .and_then(|rest| rest.find(terminator))
+    .ok_or_else(|| ParseError::line(index, line, RECORD_SHAPE))?;
+
+  let text = &line[start..start + length];
+  let value = text.parse::<u16>()
+    .map_err(|_| ParseError::new(index, line, start, text, "an unsigned integer"))?;
+
+  Ok((value, start + length + terminator.len_utf8()))
+}
+
+fn parse_record(index: usize, line: &str) -> Result<Record, ParseError> {
+  if !line.starts_with('[') {
+    return Err(ParseError::line(index, line, RECORD_SHAPE));
+  }
+
+  let (year, offset) = timestamp_field(index, line, 1, '-')?;
+  let (month, offset) = timestamp_field(index, line, offset, '-')?;
+  let (day, offset) = timestamp_field(index, line, offset, ' ')?;
+  let (hour, offset) = timestamp_field(index, line, offset, ':')?;
+  let (minute, offset) = timestamp_field(index, line, offset, ']')?;
+
+  let time = Time { year, month, day, hour, minute };
+
+  if !time.is_valid() {
+    return Err(ParseError::new(index, line, 0, &line[..offset], "a valid date and time"));
+  }
+
+  let message_start = offset + 1;
+  let message = match line.get(offset..) {
+    Some(rest) if rest.starts_with(' ') => &line[message_start..],
+    _ => return Err(ParseError::line(index, line, RECORD_SHAPE))
+  };
+
+  let event = match message {
+    "falls asleep" => RecordEvent::FallsAsleep,
+    "wakes up" => RecordEvent::WakesUp,
+    _ => match message.strip_prefix("Guard #").and_then(|m| m.strip_suffix(" begins shift")) {
+      Some(id) => {
+        let id_start = message_start + "Guard #".len();
+        let guard = id.parse::<u64>()
+          .map_err(|_| ParseError::new(index, line, id_start, id, "a guard number"))?;
+
+        RecordEvent::BeginShift(guard)
+      },
+      None => return Err(ParseError::new(index, line, message_start, message, EVENT_SHAPE))
     }
+  };
+
+  Ok(Record { time, event })
+}
+
+#[aoc_generator(day4)]
+pub fn record_parser(input: &str) -> Result<Vec<Record>, ParseError> {
+  input.lines()
+    .enumerate()
+    .map(|(index, line)| parse_record(index, line))
+    .collect()
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Anomaly {
+  /// Two records share a timestamp, so their order is ambiguous.
+  DuplicateTimestamp { first: RecordEvent, second: RecordEvent },
+  /// A guard fell asleep or woke up before any guard began a shift.
+  BeforeFirstShift { event: RecordEvent },
+  /// A guard fell asleep again without waking up first.
+  DoubleSleep { guard: u64, asleep_since: Time },
+  /// A guard woke up without having fallen asleep.
+  OrphanWake { guard: u64 },
+  /// A guard's shift ended, either by another shift starting or by the log ending, while they
+  /// were still asleep.
+  ShiftEndedAsleep { guard: u64, asleep_since: Time }
+}
+
+/// Something suspicious in a guard log, found at `time`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Finding {
+  pub time: Time,
+  pub anomaly: Anomaly
+}
+
+/// Checks the structure of a log before trusting `process` with it. The records are sorted first,
+/// the same way `process` sorts them, and the findings come back in time order.
+pub fn validate(records: &[Record]) -> Vec<Finding> {
+  let mut records = records.to_vec();
+  records.sort();
+
+  let mut findings = Vec::new();
+  let mut guard = None;
+  let mut asleep_since = None;
+  let mut previous: Option<Record> = None;
 
-    for cap in FALLS_ASLEEP_RE.captures_iter(line) {
-      records.push(Record {
-        time: Time {
-          year: cap[1].parse::<u16>().unwrap(),
-          month: cap[2].parse::<u16>().unwrap(),
-          day: cap[3].parse::<u16>().unwrap(),
-          hour: cap[4].parse::<u16>().unwrap(),
-          minute: cap[5].parse::<u16>().unwrap(),
-        },
-        event: RecordEvent::FallsAsleep
-      });
+  for record in records.iter() {
+    if let Some(previous) = previous {
+      if previous.time == record.time {
+        findings.push(Finding {
+          time: record.time,
+          anomaly: Anomaly::DuplicateTimestamp { first: previous.event, second: record.event }
+        });
+      }
     }
 
-    for cap in WAKES_UP_RE.captures_iter(line) {
-      records.push(Record {
-        time: Time {
-          year: cap[1].parse::<u16>().unwrap(),
-          month: cap[2].parse::<u16>().unwrap(),
-          day: cap[3].parse::<u16>().unwrap(),
-          hour: cap[4].parse::<u16>().unwrap(),
-          minute: cap[5].parse::<u16>().unwrap(),
-        },
-        event: RecordEvent::WakesUp
-      });
+    previous = Some(*record);
+
+    match (record.event, guard) {
+      (RecordEvent::BeginShift(id), _) => {
+        if let (Some(guard), Some(asleep_since)) = (guard, asleep_since) {
+          findings.push(Finding { time: record.time, anomaly: Anomaly::ShiftEndedAsleep { guard, asleep_since } });
+        }
+
+        guard = Some(id);
+        asleep_since = None;
+      },
+      (event, None) => {
+        findings.push(Finding { time: record.time, anomaly: Anomaly::BeforeFirstShift { event } });
+      },
+      (RecordEvent::FallsAsleep, Some(guard)) => match asleep_since {
+        Some(since) => findings.push(Finding { time: record.time, anomaly: Anomaly::DoubleSleep { guard, asleep_since: since } }),
+        None => asleep_since = Some(record.time)
+      },
+      (RecordEvent::WakesUp, Some(guard)) => match asleep_since {
+        Some(_) => asleep_since = None,
+        None => findings.push(Finding { time: record.time, anomaly: Anomaly::OrphanWake { guard } })
+      }
     }
   }
 
-  records
+  if let (Some(guard), Some(asleep_since), Some(last)) = (guard, asleep_since, records.last()) {
+    findings.push(Finding { time: last.time, anomaly: Anomaly::ShiftEndedAsleep { guard, asleep_since } });
+  }
+
+  findings
 }
 
-pub fn process(records: &Vec<Record>) -> (HashMap<u64, isize>, HashMap<u64, HashMap<u16, usize>>) {
-  let mut records = records.clone();
+/// What `process_with` should do with a sleep that spans more than one calendar day.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CrossingSpans {
+  /// Attribute each minute of the span to the day it falls on.
+  Split,
+  /// Split the span like `Split`, but also list it in `SleepTotals::warnings`.
+  Warn,
+  /// Stop processing and return the span as an error.
+  Reject
+}
+
+/// A guard's sleep, from the minute they fell asleep up to the minute they woke.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SleepSpan {
+  pub guard: u64,
+  pub start: Time,
+  pub end: Time
+}
+
+impl SleepSpan {
+  pub fn crosses_days(&self) -> bool {
+    self.start < self.end && self.start.date() != self.end.pred().date()
+  }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SleepTotals {
+  /// Total minutes asleep per guard.
+  pub time_asleep: HashMap<u64, isize>,
+  /// Per guard, how many times they were asleep during each minute of the hour.
+  pub minutes_asleep: HashMap<u64, HashMap<u16, usize>>,
+  /// Per guard, minutes asleep on each calendar day, keyed by that day's midnight.
+  pub days_asleep: HashMap<u64, HashMap<Time, isize>>,
+  /// Number of shifts each guard began.
+  pub shifts: HashMap<u64, usize>,
+  /// Per guard, how many of their shifts they spent asleep during each minute of the hour.
+  pub shifts_asleep: HashMap<u64, HashMap<u16, usize>>,
+  /// Spans that crossed a day boundary, when processed with `CrossingSpans::Warn`.
+  pub warnings: Vec<SleepSpan>
+}
+
+pub fn process(records: &[Record]) -> (HashMap<u64, isize>, HashMap<u64, HashMap<u16, usize>>) {
+  let totals = process_with(records, CrossingSpans::Split)
+    .expect("splitting never rejects a span");
+
+  (totals.time_asleep, totals.minutes_asleep)
+}
+
+pub fn process_with(records: &[Record], crossing_spans: CrossingSpans) -> Result<SleepTotals, SleepSpan> {
+  let mut records = records.to_vec();
   records.sort();
 
   let mut events:HashMap<u64, Vec<Record>> = HashMap::new();
@@ -195,90 +488,295 @@ pub fn process(records: &Vec<Record>) -> (HashMap<u64, isize>, HashMap<u64, Hash
   let mut guard = 0;
 
   for record in records {
-    //
-    assert_eq!(record.time.year, 1518);
-
     if let RecordEvent::BeginShift(id) = record.event {
       guard = id;
     }
 
-    let entry = events.entry(guard).or_insert(Vec::new());
+    let entry = events.entry(guard).or_default();
     entry.push(record);
   }
 
-  let mut time_asleep = HashMap::new();
-  let mut minutes_asleep = HashMap::new();
+  let mut totals = SleepTotals::default();
 
   for (guard, records) in events {
-    let iter = records.iter();
-
     let mut sleep_time = None;
+    let mut shift_minutes = HashSet::new();
 
-    for record in iter {
-      if record.event == RecordEvent::FallsAsleep {
-        sleep_time = Some(record.time);
-      } else if record.event == RecordEvent::WakesUp && sleep_time.is_some(){
-        let sleep_time = sleep_time.unwrap();
+    for record in records {
+      if let RecordEvent::BeginShift(_) = record.event {
+        // a guard still asleep when their shift ended doesn't carry that sleep into the next one
+        sleep_time = None;
+        *totals.shifts.entry(guard).or_insert(0) += 1;
 
-        if sleep_time.day == record.time.day && sleep_time.month == record.time.month {
-          let diff = record.time - sleep_time;
+        for minute in shift_minutes.drain() {
+          *totals.shifts_asleep.entry(guard).or_default().entry(minute).or_insert(0) += 1;
+        }
 
-          *time_asleep.entry(guard).or_insert(0) += diff;
+      } else if record.event == RecordEvent::FallsAsleep {
+        sleep_time = Some(record.time);
+      } else if let (RecordEvent::WakesUp, Some(start)) = (record.event, sleep_time) {
+        let span = SleepSpan { guard, start, end: record.time };
 
-          for t in sleep_time..record.time {
-            *minutes_asleep
-              .entry(guard).or_insert(HashMap::new())
-              .entry(t.minute).or_insert(0) += 1;
+        if span.crosses_days() {
+          match crossing_spans {
+            CrossingSpans::Split => {},
+            CrossingSpans::Warn => totals.warnings.push(span),
+            CrossingSpans::Reject => return Err(span)
           }
         }
+
+        *totals.time_asleep.entry(guard).or_insert(0) += span.end - span.start;
+
+        for t in span.start.until(span.end) {
+          *totals.minutes_asleep
+            .entry(guard).or_default()
+            .entry(t.minute).or_insert(0) += 1;
+
+          *totals.days_asleep
+            .entry(guard).or_default()
+            .entry(t.date()).or_insert(0) += 1;
+
+          shift_minutes.insert(t.minute);
+        }
+
+        sleep_time = None;
       }
     }
+
+    for minute in shift_minutes {
+      *totals.shifts_asleep.entry(guard).or_default().entry(minute).or_insert(0) += 1;
+    }
   }
 
-  (time_asleep, minutes_asleep)
+  totals.warnings.sort_by_key(|span| span.start);
+
+  Ok(totals)
 }
 
-#[aoc(day4, part1)]
-pub fn part1(records: &Vec<Record>) -> usize {
+/// Everything we know about one guard's sleeping habits.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GuardStats {
+  pub guard: u64,
+  pub shifts: usize,
+  /// Total minutes asleep.
+  pub total_sleep: isize,
+  /// How many times the guard was asleep during each minute of the hour.
+  pub minutes: [usize; 60],
+  /// How many shifts the guard spent asleep during each minute of the hour.
+  pub shifts_asleep: [usize; 60]
+}
 
-  let (time_asleep, minutes_asleep) = process(records);
+impl GuardStats {
+  /// The minute the guard was most often asleep, with the fraction of their shifts they were
+  /// asleep for it. Ties go to the earlier minute, and a guard who never slept has no such minute.
+  pub fn most_likely_minute(&self) -> Option<(u16, f64)> {
+    let (minute, count) = self.minutes.iter().enumerate()
+      .max_by(|(a_minute, a), (b_minute, b)| a.cmp(b).then(b_minute.cmp(a_minute)))?;
 
-  let (best_guard,_) = time_asleep.iter()
-    .max_by(|(_,x),(_,y)| x.cmp(y))
-    .unwrap();
+    if *count == 0 {
+      return None;
+    }
 
-  let (best_minute,_) = minutes_asleep.get(best_guard).unwrap().iter()
-    .max_by(|(_,x),(_,y)| x.cmp(y))
-    .unwrap();
+    let confidence = if self.shifts == 0 {
+      0.0
+    } else {
+      self.shifts_asleep[minute] as f64 / self.shifts as f64
+    };
 
-  (*best_guard as usize) * (*best_minute as usize)
+    Some((minute as u16, confidence))
+  }
+
+  pub fn average_sleep_per_shift(&self) -> f64 {
+    if self.shifts == 0 {
+      0.0
+    } else {
+      self.total_sleep as f64 / self.shifts as f64
+    }
+  }
 }
 
-#[aoc(day4, part2)]
-pub fn part2(records: &Vec<Record>) -> usize {
-  let (_time_asleep, minutes_asleep) = process(records);
-
-  let mut best_count: usize = 0;
-  let mut best_guard: usize = 0;
-  let mut best_minute: usize = 0;
-
-  for (guard, map) in minutes_asleep {
-    for (minute, count) in map {
-      if count > best_count {
-        best_count = count;
-        best_guard = guard as usize;
-        best_minute = minute as usize;
+/// Builds the stats for every guard in the log, ordered by guard ID.
+pub fn guard_stats(records: &[Record]) -> Vec<GuardStats> {
+  let totals = process_with(records, CrossingSpans::Split)
+    .expect("splitting never rejects a span");
+
+  let mut guards = totals.shifts.keys()
+    .chain(totals.time_asleep.keys())
+    .cloned()
+    .collect::<Vec<u64>>();
+  guards.sort();
+  guards.dedup();
+
+  let histogram = |map: Option<&HashMap<u16, usize>>| {
+    let mut histogram = [0; 60];
+
+    for (minute, count) in map.into_iter().flatten() {
+      histogram[*minute as usize] = *count;
+    }
+
+    histogram
+  };
+
+  guards.into_iter()
+    .map(|guard| GuardStats {
+      guard,
+      shifts: totals.shifts.get(&guard).cloned().unwrap_or(0),
+      total_sleep: totals.time_asleep.get(&guard).cloned().unwrap_or(0),
+      minutes: histogram(totals.minutes_asleep.get(&guard)),
+      shifts_asleep: histogram(totals.shifts_asleep.get(&guard))
+    })
+    .collect()
+}
+
+/// A way of picking the sleepiest guard. Higher scores rank first.
+pub trait Strategy {
+  fn score(&self, stats: &GuardStats) -> f64;
+}
+
+impl<F: Fn(&GuardStats) -> f64> Strategy for F {
+  fn score(&self, stats: &GuardStats) -> f64 {
+    self(stats)
+  }
+}
+
+/// Strategy 1 from the puzzle: the guard with the most minutes asleep.
+pub struct MostMinutesAsleep;
+
+impl Strategy for MostMinutesAsleep {
+  fn score(&self, stats: &GuardStats) -> f64 {
+    stats.total_sleep as f64
+  }
+}
+
+/// Strategy 2 from the puzzle: the guard most frequently asleep on the same minute.
+pub struct MostFrequentMinute;
+
+impl Strategy for MostFrequentMinute {
+  fn score(&self, stats: &GuardStats) -> f64 {
+    stats.minutes.iter().max().cloned().unwrap_or(0) as f64
+  }
+}
+
+/// Orders guards from highest to lowest score, breaking ties by guard ID.
+pub fn rank<'a, S: Strategy>(stats: &'a [GuardStats], strategy: &S) -> Vec<&'a GuardStats> {
+  let mut ranked = stats.iter()
+    .map(|s| (strategy.score(s), s))
+    .collect::<Vec<(f64, &GuardStats)>>();
+
+  ranked.sort_by(|(a_score, a), (b_score, b)| {
+    b_score.partial_cmp(a_score).unwrap_or(Ordering::Equal).then(a.guard.cmp(&b.guard))
+  });
+
+  ranked.into_iter().map(|(_, s)| s).collect()
+}
+
+const MINUTE_HEADER_TENS: &str = "000000000011111111112222222222333333333344444444445555555555";
+const MINUTE_HEADER_ONES: &str = "012345678901234567890123456789012345678901234567890123456789";
+
+/// Draws the log the way the puzzle does: one row per shift, dated by the midnight hour the shift
+/// covers, with `#` for each minute of that hour the guard spent asleep.
+///
+/// ```text
+/// Date   ID   Minute
+///             000000000011111111112222222222333333333344444444445555555555
+///             012345678901234567890123456789012345678901234567890123456789
+/// 11-01  #10  .....####################.....#########################.....
+/// ```
+pub fn render_timeline(records: &[Record]) -> String {
+  let mut records = records.to_vec();
+  records.sort();
+
+  let mut rows: Vec<(Time, u64, [bool; 60])> = Vec::new();
+  let mut sleep_time = None;
+
+  for record in records {
+    match record.event {
+      RecordEvent::BeginShift(guard) => {
+        let day = if record.time.hour == 0 {
+          record.time.date()
+        } else {
+          (record.time + 24 * 60).date()
+        };
+
+        rows.push((day, guard, [false; 60]));
+        sleep_time = None;
+      },
+      RecordEvent::FallsAsleep => sleep_time = Some(record.time),
+      RecordEvent::WakesUp => {
+        if let (Some((day, _, minutes)), Some(start)) = (rows.last_mut(), sleep_time) {
+          for t in start.until(record.time) {
+            if t.date() == *day && t.hour == 0 {
+              minutes[t.minute as usize] = true;
+            }
+          }
+        }
+
+        sleep_time = None;
       }
     }
   }
 
-  best_guard * best_minute
+  let id_width = rows.iter().map(|(_, guard, _)| guard.to_string().len() + 1).max().unwrap_or(2).max(2);
+  let pad = " ".repeat(7 + id_width + 2);
+
+  let mut out = format!("Date   {:width$}  Minute\n", "ID", width = id_width);
+  out += &format!("{}{}\n{}{}\n", pad, MINUTE_HEADER_TENS, pad, MINUTE_HEADER_ONES);
+
+  for (day, guard, minutes) in rows {
+    let cells = minutes.iter().map(|asleep| if *asleep { '#' } else { '.' }).collect::<String>();
+    out += &format!("{:02}-{:02}  {:width$}  {}\n", day.month, day.day, format!("#{}", guard), cells, width = id_width);
+  }
+
+  out
+}
+
+/// Draws each guard's minute histogram as a row of digits, from `.` for never asleep up to `9` for
+/// the most anyone was asleep on any one minute, so rows can be compared against each other.
+pub fn render_heatmap(stats: &[GuardStats]) -> String {
+  let max = stats.iter().flat_map(|s| s.minutes.iter()).max().cloned().unwrap_or(0);
+  let id_width = stats.iter().map(|s| s.guard.to_string().len() + 1).max().unwrap_or(2).max(2);
+  let pad = " ".repeat(id_width + 2);
+
+  let mut out = format!("{}{}\n{}{}\n", pad, MINUTE_HEADER_TENS, pad, MINUTE_HEADER_ONES);
+
+  for stats in stats {
+    let cells = stats.minutes.iter()
+      .map(|count| match *count {
+        0 => '.',
+        count => std::char::from_digit(((count * 9) as f64 / max as f64).ceil() as u32, 10).unwrap()
+      })
+      .collect::<String>();
+
+    out += &format!("{:width$}  {}\n", format!("#{}", stats.guard), cells, width = id_width);
+  }
+
+  out
+}
+
+/// The best guard's ID times the minute they're most often asleep. Guards who never sleep are
+/// skipped, so this is `None` if nobody does.
+fn strategy_answer<S: Strategy>(records: &[Record], strategy: &S) -> Option<usize> {
+  let stats = guard_stats(records);
+
+  rank(&stats, strategy).into_iter()
+    .find_map(|guard| guard.most_likely_minute().map(|(minute, _)| (guard.guard as usize) * (minute as usize)))
+}
+
+#[aoc(day4, part1)]
+pub fn part1(records: &[Record]) -> Option<usize> {
+  strategy_answer(records, &MostMinutesAsleep)
+}
+
+#[aoc(day4, part2)]
+pub fn part2(records: &[Record]) -> Option<usize> {
+  strategy_answer(records, &MostFrequentMinute)
 }
 
 #[cfg(test)]
 mod tests {
   use crate::day4::*;
-  use std::cmp::Ordering;
+
+  const EXAMPLE: &str = "[1518-11-01 00:00] Guard #10 begins shift\n[1518-11-01 00:05] falls asleep\n[1518-11-01 00:25] wakes up\n[1518-11-01 00:30] falls asleep\n[1518-11-01 00:55] wakes up\n[1518-11-01 23:58] Guard #99 begins shift\n[1518-11-02 00:40] falls asleep\n[1518-11-02 00:50] wakes up\n[1518-11-03 00:05] Guard #10 begins shift\n[1518-11-03 00:24] falls asleep\n[1518-11-03 00:29] wakes up\n[1518-11-04 00:02] Guard #99 begins shift\n[1518-11-04 00:36] falls asleep\n[1518-11-04 00:46] wakes up\n[1518-11-05 00:03] Guard #99 begins shift\n[1518-11-05 00:45] falls asleep\n[1518-11-05 00:55] wakes up";
 
   #[test]
   pub fn time_ordering_test() {
@@ -308,6 +806,82 @@ mod tests {
 
   }
 
+  #[test]
+  pub fn time_stepping_test() {
+    let t = Time { year: 1518, month: 12, day: 31, hour: 23, minute: 59 };
+    let next = Time { year: 1519, month: 1, day: 1, hour: 0, minute: 0 };
+
+    assert_eq!(next, t.succ());
+    assert_eq!(t, next.pred());
+
+    let t = Time { year: 1518, month: 11, day: 1, hour: 0, minute: 59 };
+    assert_eq!(Time { year: 1518, month: 11, day: 1, hour: 1, minute: 0 }, t.succ());
+    assert_eq!(Time { year: 1518, month: 11, day: 1, hour: 0, minute: 58 }, t.pred());
+  }
+
+  #[test]
+  pub fn calendar_test() {
+    assert_eq!(28, Time { year: 1518, month: 2, day: 1, hour: 0, minute: 0 }.days_in_month());
+    assert_eq!(29, Time { year: 1600, month: 2, day: 1, hour: 0, minute: 0 }.days_in_month());
+    assert_eq!(28, Time { year: 1700, month: 2, day: 1, hour: 0, minute: 0 }.days_in_month());
+    assert_eq!(29, Time { year: 1520, month: 2, day: 1, hour: 0, minute: 0 }.days_in_month());
+    assert_eq!(31, Time { year: 1518, month: 7, day: 1, hour: 0, minute: 0 }.days_in_month());
+    assert_eq!(30, Time { year: 1518, month: 11, day: 1, hour: 0, minute: 0 }.days_in_month());
+
+    assert!(!Time { year: 1518, month: 2, day: 29, hour: 0, minute: 0 }.is_valid());
+    assert!(Time { year: 1520, month: 2, day: 29, hour: 0, minute: 0 }.is_valid());
+    assert!(!Time { year: 1518, month: 13, day: 1, hour: 0, minute: 0 }.is_valid());
+    assert!(!Time { year: 1518, month: 1, day: 1, hour: 24, minute: 0 }.is_valid());
+  }
+
+  #[test]
+  pub fn time_arithmetic_test() {
+    let feb_28 = Time { year: 1518, month: 2, day: 28, hour: 23, minute: 59 };
+    let mar_1 = Time { year: 1518, month: 3, day: 1, hour: 0, minute: 0 };
+
+    assert_eq!(mar_1, feb_28.succ());
+    assert_eq!(feb_28, mar_1.pred());
+    assert_eq!(1, mar_1 - feb_28);
+    assert_eq!(-1, feb_28 - mar_1);
+
+    let leap_feb_28 = Time { year: 1520, month: 2, day: 28, hour: 23, minute: 59 };
+    assert_eq!(Time { year: 1520, month: 2, day: 29, hour: 0, minute: 0 }, leap_feb_28.succ());
+
+    let new_year = Time { year: 1519, month: 1, day: 1, hour: 0, minute: 0 };
+    let last_year = Time { year: 1518, month: 1, day: 1, hour: 0, minute: 0 };
+    assert_eq!(365 * 24 * 60, new_year - last_year);
+    assert_eq!(new_year, last_year + 365 * 24 * 60);
+    assert_eq!(last_year, new_year - 365 * 24 * 60);
+
+    let t = Time { year: 1518, month: 11, day: 30, hour: 23, minute: 50 };
+    assert_eq!(Time { year: 1518, month: 12, day: 1, hour: 0, minute: 10 }, t + 20);
+    assert_eq!(Time { year: 1518, month: 10, day: 31, hour: 23, minute: 50 }, t - 30 * 24 * 60);
+
+    assert_eq!(0, Time { year: 1970, month: 1, day: 1, hour: 0, minute: 0 }.to_minutes());
+    assert_eq!(t, Time::from_minutes(t.to_minutes()));
+
+    let first = Time { year: 0, month: 1, day: 1, hour: 0, minute: 0 };
+    let last = Time { year: 65535, month: 12, day: 31, hour: 23, minute: 59 };
+    assert_eq!(Some(first), Time::checked_from_minutes(first.to_minutes()));
+    assert_eq!(Some(last), Time::checked_from_minutes(last.to_minutes()));
+    assert_eq!(None, Time::checked_from_minutes(first.to_minutes() - 1));
+    assert_eq!(None, Time::checked_from_minutes(last.to_minutes() + 1));
+  }
+
+  #[test]
+  pub fn time_range_test() {
+    let start = Time { year: 1518, month: 11, day: 1, hour: 23, minute: 58 };
+    let end = Time { year: 1518, month: 11, day: 2, hour: 0, minute: 2 };
+
+    let forward = start.until(end).map(|t| t.minute).collect::<Vec<u16>>();
+    let backward = start.until(end).rev().map(|t| t.minute).collect::<Vec<u16>>();
+
+    assert_eq!(vec![58, 59, 0, 1], forward);
+    assert_eq!(vec![1, 0, 59, 58], backward);
+    assert_eq!(4, start.until(end).len());
+    assert_eq!(0, end.until(start).count());
+  }
+
   #[test]
   pub fn record_parser_test() {
     assert_eq!([
@@ -317,12 +891,171 @@ mod tests {
     ],
     record_parser(
       "[1518-11-01 00:00] Guard #10 begins shift\n[1518-11-01 00:05] falls asleep\n[1518-11-01 00:25] wakes up"
-    ).as_ref());
+    ).unwrap().as_ref());
+  }
+
+  #[test]
+  pub fn record_parser_error_test() {
+    let error = record_parser("[1518-11-01 00:00] Guard #10 begins shift\n[1518-11-01 00:05] dozes off").unwrap_err();
+    assert_eq!((2, 20, "dozes off"), (error.line, error.column, error.text.as_str()));
+
+    let error = record_parser("1518-11-01 00:05 falls asleep").unwrap_err();
+    assert_eq!((1, 1), (error.line, error.column));
+
+    let error = record_parser("[1518-11-01 00:05 falls asleep").unwrap_err();
+    assert_eq!((1, 1), (error.line, error.column));
+
+    let error = record_parser("[1518-11-01 00:05]falls asleep").unwrap_err();
+    assert_eq!((1, 1), (error.line, error.column));
+
+    let error = record_parser("[1518-11-01 00:05] Guard #10 ends shift").unwrap_err();
+    assert_eq!((1, 20, "Guard #10 ends shift"), (error.line, error.column, error.text.as_str()));
+
+    let error = record_parser("[1518-11-01 00:00] Guard #ten begins shift").unwrap_err();
+    assert_eq!((1, 27, "ten"), (error.line, error.column, error.text.as_str()));
+
+    let error = record_parser("[1518-11-01 00:99999] falls asleep").unwrap_err();
+    assert_eq!((1, 16, "99999"), (error.line, error.column, error.text.as_str()));
+
+    let error = record_parser("[1518-02-29 00:05] falls asleep").unwrap_err();
+    assert_eq!((1, 1, "[1518-02-29 00:05]"), (error.line, error.column, error.text.as_str()));
+  }
+
+  #[test]
+  pub fn validate_test() {
+    let records = record_parser("[1518-11-01 00:00] falls asleep
+[1518-11-01 00:01] Guard #10 begins shift
+[1518-11-01 00:05] wakes up
+[1518-11-01 00:10] falls asleep
+[1518-11-01 00:20] falls asleep
+[1518-11-01 00:30] wakes up
+[1518-11-01 00:40] falls asleep
+[1518-11-02 00:00] Guard #99 begins shift
+[1518-11-02 00:00] falls asleep
+[1518-11-02 00:30] wakes up
+[1518-11-02 00:45] falls asleep").unwrap();
+
+    let at = |day, minute| Time { year: 1518, month: 11, day, hour: 0, minute };
+
+    assert_eq!(vec![
+      Finding { time: at(1, 0), anomaly: Anomaly::BeforeFirstShift { event: RecordEvent::FallsAsleep } },
+      Finding { time: at(1, 5), anomaly: Anomaly::OrphanWake { guard: 10 } },
+      Finding { time: at(1, 20), anomaly: Anomaly::DoubleSleep { guard: 10, asleep_since: at(1, 10) } },
+      Finding { time: at(2, 0), anomaly: Anomaly::ShiftEndedAsleep { guard: 10, asleep_since: at(1, 40) } },
+      Finding { time: at(2, 0), anomaly: Anomaly::DuplicateTimestamp {
+        first: RecordEvent::BeginShift(99),
+        second: RecordEvent::FallsAsleep
+      } },
+      Finding { time: at(2, 45), anomaly: Anomaly::ShiftEndedAsleep { guard: 99, asleep_since: at(2, 45) } },
+    ], validate(&records));
+  }
+
+  #[test]
+  pub fn crossing_midnight_test() {
+    let records = record_parser("[1518-12-31 23:00] Guard #10 begins shift\n[1518-12-31 23:58] falls asleep\n[1519-01-01 00:02] wakes up").unwrap();
+    let totals = process_with(&records, CrossingSpans::Split).unwrap();
+
+    let new_years_eve = Time { year: 1518, month: 12, day: 31, hour: 0, minute: 0 };
+    let new_years_day = Time { year: 1519, month: 1, day: 1, hour: 0, minute: 0 };
+
+    assert_eq!(Some(&4), totals.time_asleep.get(&10));
+    assert_eq!(Some(&2), totals.days_asleep[&10].get(&new_years_eve));
+    assert_eq!(Some(&2), totals.days_asleep[&10].get(&new_years_day));
+
+    let mut minutes = totals.minutes_asleep[&10].keys().cloned().collect::<Vec<u16>>();
+    minutes.sort();
+    assert_eq!(vec![0, 1, 58, 59], minutes);
+    assert!(totals.warnings.is_empty());
+
+    let span = SleepSpan {
+      guard: 10,
+      start: Time { year: 1518, month: 12, day: 31, hour: 23, minute: 58 },
+      end: Time { year: 1519, month: 1, day: 1, hour: 0, minute: 2 }
+    };
+    assert_eq!(vec![span], process_with(&records, CrossingSpans::Warn).unwrap().warnings);
+    assert_eq!(span, process_with(&records, CrossingSpans::Reject).unwrap_err());
+  }
+
+  #[test]
+  pub fn waking_at_midnight_test() {
+    let records = record_parser("[1518-02-28 23:00] Guard #10 begins shift\n[1518-02-28 23:50] falls asleep\n[1518-03-01 00:00] wakes up").unwrap();
+    let totals = process_with(&records, CrossingSpans::Reject).unwrap();
+
+    assert_eq!(Some(&10), totals.time_asleep.get(&10));
+    assert_eq!(1, totals.days_asleep[&10].len());
+  }
+
+  #[test]
+  pub fn shift_ended_asleep_test() {
+    let records = record_parser("[1518-11-01 00:00] Guard #10 begins shift\n[1518-11-01 00:40] falls asleep\n[1518-11-02 00:00] Guard #99 begins shift\n[1518-11-05 00:00] Guard #10 begins shift\n[1518-11-05 00:30] wakes up").unwrap();
+    let totals = process_with(&records, CrossingSpans::Reject).unwrap();
+
+    assert_eq!(None, totals.time_asleep.get(&10));
+    assert_eq!(None, totals.days_asleep.get(&10));
+    assert_eq!(Some(&2), totals.shifts.get(&10));
+  }
+
+  #[test]
+  pub fn guard_stats_test() {
+    let stats = guard_stats(&record_parser(EXAMPLE).unwrap());
+
+    assert_eq!(vec![10, 99], stats.iter().map(|s| s.guard).collect::<Vec<u64>>());
+
+    let (guard_10, guard_99) = (&stats[0], &stats[1]);
+    assert_eq!((2, 50), (guard_10.shifts, guard_10.total_sleep));
+    assert_eq!((3, 30), (guard_99.shifts, guard_99.total_sleep));
+    assert_eq!(Some((24, 1.0)), guard_10.most_likely_minute());
+    assert_eq!(Some((45, 1.0)), guard_99.most_likely_minute());
+    assert_eq!(25.0, guard_10.average_sleep_per_shift());
+    assert_eq!(10.0, guard_99.average_sleep_per_shift());
+
+    let ranked = |ranking: Vec<&GuardStats>| ranking.iter().map(|s| s.guard).collect::<Vec<u64>>();
+    assert_eq!(vec![10, 99], ranked(rank(&stats, &MostMinutesAsleep)));
+    assert_eq!(vec![99, 10], ranked(rank(&stats, &MostFrequentMinute)));
+    assert_eq!(vec![99, 10], ranked(rank(&stats, &|s: &GuardStats| -s.average_sleep_per_shift())));
+  }
+
+  #[test]
+  pub fn render_timeline_test() {
+    assert_eq!("Date   ID   Minute
+            000000000011111111112222222222333333333344444444445555555555
+            012345678901234567890123456789012345678901234567890123456789
+11-01  #10  .....####################.....#########################.....
+11-02  #99  ........................................##########..........
+11-03  #10  ........................#####...............................
+11-04  #99  ....................................##########..............
+11-05  #99  .............................................##########.....
+", render_timeline(&record_parser(EXAMPLE).unwrap()));
+  }
+
+  #[test]
+  pub fn render_heatmap_test() {
+    assert_eq!("     000000000011111111112222222222333333333344444444445555555555
+     012345678901234567890123456789012345678901234567890123456789
+#10  .....333333333333333333363333.3333333333333333333333333.....
+#99  ....................................3333666669666633333.....
+", render_heatmap(&guard_stats(&record_parser(EXAMPLE).unwrap())));
   }
 
   #[test]
   pub fn part1_test() {
-    let records = record_parser("[1518-11-01 00:00] Guard #10 begins shift\n[1518-11-01 00:05] falls asleep\n[1518-11-01 00:25] wakes up\n[1518-11-01 00:30] falls asleep\n[1518-11-01 00:55] wakes up\n[1518-11-01 23:58] Guard #99 begins shift\n[1518-11-02 00:40] falls asleep\n[1518-11-02 00:50] wakes up\n[1518-11-03 00:05] Guard #10 begins shift\n[1518-11-03 00:24] falls asleep\n[1518-11-03 00:29] wakes up\n[1518-11-04 00:02] Guard #99 begins shift\n[1518-11-04 00:36] falls asleep\n[1518-11-04 00:46] wakes up\n[1518-11-05 00:03] Guard #99 begins shift\n[1518-11-05 00:45] falls asleep\n[1518-11-05 00:55] wakes up");
-    assert_eq!(240, part1(&records));
+    let records = record_parser(EXAMPLE).unwrap();
+    assert_eq!(Some(240), part1(&records));
+    assert!(validate(&records).is_empty());
+  }
+
+  #[test]
+  pub fn part2_test() {
+    assert_eq!(Some(4455), part2(&record_parser(EXAMPLE).unwrap()));
+  }
+
+  #[test]
+  pub fn nobody_sleeps_test() {
+    assert_eq!(None, part1(&[]));
+    assert_eq!(None, part2(&[]));
+
+    let records = record_parser("[1518-11-01 00:00] Guard #10 begins shift\n[1518-11-02 00:00] Guard #99 begins shift").unwrap();
+    assert_eq!(None, part1(&records));
+    assert_eq!(None, part2(&records));
   }
 }
\ No newline at end of file
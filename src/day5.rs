@@ -1,4 +1,7 @@
 use aoc_runner_derive::{aoc, aoc_generator};
+use crate::parse::ParseError;
+use std::collections::HashMap;
+use std::io::{self, Read};
 
 #[derive(Eq, PartialEq)]
 pub enum Polarity {
@@ -6,14 +9,34 @@ pub enum Polarity {
   Negative
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct Unit(char);
 
 impl Unit {
+  pub fn new(symbol: char) -> Unit {
+    Unit(symbol)
+  }
+
+  pub fn symbol(&self) -> char {
+    self.0
+  }
+
+  /// Identifies the unit's type: its lowercase form, or the unit itself if it doesn't lowercase to
+  /// exactly one character (like 'İ', which lowercases to "i̇"). Two units of opposite polarity
+  /// react when their types match, so 'ẞ' reacts with 'ß' and the Kelvin sign with 'k'.
   pub fn letter(&self) -> char {
-    // let's assume no unicode weirdness happens where lowercasing one character turns it into
-    // multiple characters.
-    self.0.to_lowercase().next().unwrap()
+    let mut lower = self.0.to_lowercase();
+
+    match (lower.next(), lower.next()) {
+      (Some(c), None) => c,
+      _ => self.0
+    }
+  }
+
+  /// Whether the unit has a polarity at all. Caseless characters like digits or CJK can't take
+  /// part in the puzzle's reactions.
+  pub fn is_cased(&self) -> bool {
+    self.0.is_lowercase() || self.0.is_uppercase()
   }
 
   pub fn polarity(&self) -> Polarity {
@@ -28,66 +51,431 @@ impl Unit {
   }
 }
 
-#[aoc_generator(day5)]
-pub fn polymer_parser(input: &str) -> Vec<Unit> {
+/// Decides which units destroy each other. The reducer pushes units onto a stack one at a time and
+/// asks the rules about the top of the stack after every push, so a reaction is found as soon as its
+/// last unit arrives.
+pub trait ReactionRules {
+  /// How many units at the end of `stack` react away, if any do.
+  fn reaction(&self, stack: &[Unit]) -> Option<usize>;
+
+  /// Whether `unit` can appear in a polymer under these rules at all.
+  fn accepts(&self, _unit: &Unit) -> bool {
+    true
+  }
+}
+
+/// The puzzle's rule: the same letter in opposite cases reacts.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CaseRule;
+
+impl ReactionRules for CaseRule {
+  fn reaction(&self, stack: &[Unit]) -> Option<usize> {
+    match stack {
+      [.., a, b] if a.reacts_with(b) => Some(2),
+      _ => None
+    }
+  }
+
+  fn accepts(&self, unit: &Unit) -> bool {
+    unit.is_cased()
+  }
+}
+
+/// A table of arbitrary patterns, each a sequence of units that vanishes when it appears in the
+/// polymer. When patterns overlap, the first one to be completed reacts.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RuleTable {
+  // patterns grouped by their last unit, since that's the one that completes them.
+  patterns: HashMap<char, Vec<Vec<char>>>
+}
+
+impl RuleTable {
+  pub fn new() -> RuleTable {
+    RuleTable::default()
+  }
+
+  pub fn add(&mut self, pattern: &[char]) {
+    if let Some(last) = pattern.last() {
+      self.patterns.entry(*last).or_default().push(pattern.to_vec());
+    }
+  }
 
+  /// Reads a rules file: one pattern per line, written as the units that react, e.g. `aA` or
+  /// `+-` or `xyz`. Blank lines and lines starting with `#` are ignored, and surrounding whitespace
+  /// is trimmed, so patterns can't contain spaces at either end.
+  pub fn parse(input: &str) -> Result<RuleTable, ParseError> {
+    let mut table = RuleTable::new();
+
+    for (index, line) in input.lines().enumerate() {
+      let pattern = line.trim();
+
+      if pattern.is_empty() || pattern.starts_with('#') {
+        continue;
+      }
+
+      if pattern.chars().any(char::is_whitespace) {
+        let offset = line.find(pattern).unwrap();
+        return Err(ParseError::new(index, line, offset, pattern, "a pattern without whitespace in it"));
+      }
+
+      table.add(&pattern.chars().collect::<Vec<char>>());
+    }
+
+    Ok(table)
+  }
+}
+
+impl ReactionRules for RuleTable {
+  fn reaction(&self, stack: &[Unit]) -> Option<usize> {
+    let last = stack.last()?;
+
+    self.patterns.get(&last.0)?.iter()
+      .find(|pattern| {
+        pattern.len() <= stack.len() &&
+          stack[stack.len() - pattern.len()..].iter().zip(pattern.iter()).all(|(unit, c)| unit.0 == *c)
+      })
+      .map(|pattern| pattern.len())
+  }
+}
+
+/// Reads a polymer for the puzzle's case rule. Whitespace anywhere is ignored, and characters that
+/// don't have a case are rejected since they could never react.
+#[aoc_generator(day5)]
+pub fn polymer_parser(input: &str) -> Result<Vec<Unit>, ParseError> {
   let mut polymer = Vec::with_capacity(input.len());
 
-  for c in input.trim().chars() {
-    polymer.push(Unit(c));
+  for (index, line) in input.lines().enumerate() {
+    for (offset, c) in line.char_indices() {
+      let unit = Unit(c);
+
+      if c.is_whitespace() {
+        continue;
+      }
+
+      if !unit.is_cased() {
+        return Err(ParseError::new(index, line, offset, &line[offset..offset + c.len_utf8()], "a letter with an upper and lower case"));
+      }
+
+      polymer.push(unit);
+    }
   }
 
-  polymer
+  Ok(polymer)
 }
 
-#[aoc(day5, part1)]
-pub fn part1(input: &Vec<Unit>) -> usize {
-  let mut polymer = input.clone();
+/// Reads a polymer of arbitrary symbols for use with custom `ReactionRules`, ignoring whitespace.
+pub fn unit_parser(input: &str) -> Vec<Unit> {
+  input.chars()
+    .filter(|c| !c.is_whitespace())
+    .map(Unit)
+    .collect()
+}
+
+/// Fully reacts a polymer in one pass. Each unit either reacts with the unit on top of the stack of
+/// survivors so far, destroying both, or joins the stack itself.
+pub fn reduce(polymer: &[Unit]) -> Vec<Unit> {
+  reduce_with(polymer, &CaseRule)
+}
+
+/// Like `reduce`, but under any reaction rules.
+pub fn reduce_with<R: ReactionRules>(polymer: &[Unit], rules: &R) -> Vec<Unit> {
+  let mut reducer = StreamingReducer::with_capacity(rules, polymer.len());
+
+  for unit in polymer {
+    reducer.push(*unit);
+  }
+
+  reducer.into_units()
+}
+
+/// Reduces a polymer as it arrives, one unit at a time, without ever holding more of it than the
+/// units that haven't reacted yet. Once a reaction has been removed from the top of the stack,
+/// what's left was already checked when it was built, so one check per unit is enough.
+pub struct StreamingReducer<'a, R: ReactionRules> {
+  rules: &'a R,
+  stack: Vec<Unit>,
+  consumed: usize
+}
+
+impl<'a, R: ReactionRules> StreamingReducer<'a, R> {
+  pub fn new(rules: &'a R) -> Self {
+    StreamingReducer::with_capacity(rules, 0)
+  }
+
+  pub fn with_capacity(rules: &'a R, capacity: usize) -> Self {
+    StreamingReducer { rules, stack: Vec::with_capacity(capacity), consumed: 0 }
+  }
+
+  pub fn push(&mut self, unit: Unit) {
+    self.consumed += 1;
+    self.stack.push(unit);
+
+    if let Some(length) = self.rules.reaction(&self.stack) {
+      self.stack.truncate(self.stack.len() - length);
+    }
+  }
+
+  /// Pushes every non-whitespace character as a unit, stopping at the first one the rules don't
+  /// accept and returning it.
+  pub fn push_chars<I: IntoIterator<Item=char>>(&mut self, chars: I) -> Result<(), Unit> {
+    for c in chars {
+      let unit = Unit(c);
+
+      if c.is_whitespace() {
+        continue;
+      }
+
+      if !self.rules.accepts(&unit) {
+        return Err(unit);
+      }
+
+      self.push(unit);
+    }
+
+    Ok(())
+  }
+
+  /// Reads UTF-8 text to the end in fixed-size chunks, calling `progress` after each one.
+  pub fn read_from<Rd: Read, F: FnMut(&Self)>(&mut self, mut reader: Rd, mut progress: F) -> io::Result<()> {
+    let mut buffer = vec![0; 64 * 1024];
+    // bytes of a character that was split across two chunks.
+    let mut pending = 0;
+
+    loop {
+      let read = reader.read(&mut buffer[pending..])?;
+
+      if read == 0 {
+        if pending > 0 {
+          return Err(io::Error::new(io::ErrorKind::InvalidData, "polymer ends partway through a UTF-8 character"));
+        }
+
+        return Ok(());
+      }
+
+      let filled = pending + read;
+      let valid = match std::str::from_utf8(&buffer[..filled]) {
+        Ok(text) => text.len(),
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e))
+      };
+
+      self.push_chars(std::str::from_utf8(&buffer[..valid]).unwrap().chars())
+        .map_err(|unit| io::Error::new(io::ErrorKind::InvalidData, format!("{:?} can't be part of this polymer", unit.0)))?;
+
+      buffer.copy_within(valid..filled, 0);
+      pending = filled - valid;
+
+      progress(self);
+    }
+  }
+
+  /// The number of units read so far.
+  pub fn consumed(&self) -> usize {
+    self.consumed
+  }
+
+  /// The length of everything read so far, fully reacted.
+  pub fn len(&self) -> usize {
+    self.stack.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.stack.is_empty()
+  }
+
+  pub fn units(&self) -> &[Unit] {
+    &self.stack
+  }
+
+  pub fn into_units(self) -> Vec<Unit> {
+    self.stack
+  }
+}
+
+/// One reaction recorded by `reduce_traced`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReactionEvent {
+  /// This was the `step`th reaction, counting from zero.
+  pub step: usize,
+  /// Position in the original polymer of the unit whose arrival set off the reaction.
+  pub trigger: usize,
+  /// Positions in the original polymer of the destroyed units, in polymer order.
+  pub positions: Vec<usize>,
+  /// The destroyed units, in polymer order.
+  pub units: Vec<Unit>
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Trace {
+  pub events: Vec<ReactionEvent>
+}
 
-  loop {
-    let mut result = Vec::new();
+impl Trace {
+  /// Writes the trace as tab-separated text with a header row, one reaction per line.
+  pub fn export(&self) -> String {
+    let mut out = String::from("step\ttrigger\tpositions\tunits\n");
 
-    let mut iter = polymer.iter().peekable();
+    for event in self.events.iter() {
+      let positions = event.positions.iter().map(|p| p.to_string()).collect::<Vec<String>>();
+      let units = event.units.iter().map(|u| u.0).collect::<String>();
 
-    while let Some(first) = iter.next() {
-      if let Some(second) = iter.peek() {
-        if first.reacts_with(second) {
-          // destroy the second too
-          iter.next();
-        } else {
-          result.push(*first);
+      out += &format!("{}\t{}\t{}\t{}\n", event.step, event.trigger, positions.join(" "), units);
+    }
+
+    out
+  }
+
+  /// Rebuilds the polymer as it was after the first `steps` reactions. Positions past the end of
+  /// the polymer are ignored; use `verify` to check the trace matches it.
+  pub fn replay(&self, polymer: &[Unit], steps: usize) -> Vec<Unit> {
+    let mut destroyed = vec![false; polymer.len()];
+
+    for event in self.events.iter().take(steps) {
+      for position in event.positions.iter() {
+        if let Some(destroyed) = destroyed.get_mut(*position) {
+          *destroyed = true;
         }
-      } else {
-        result.push(*first);
       }
     }
 
-    if result.len() == polymer.len() {
-      return result.len();
+    polymer.iter().zip(destroyed)
+      .filter(|(_, destroyed)| !destroyed)
+      .map(|(unit, _)| *unit)
+      .collect()
+  }
+
+  /// Checks that every reaction in the trace could really have happened: its units were still
+  /// there, next to each other, and match what the trace says. Returns the first bad step.
+  pub fn verify(&self, polymer: &[Unit]) -> Result<(), usize> {
+    let mut destroyed = vec![false; polymer.len()];
+
+    for event in self.events.iter() {
+      let valid = !event.positions.is_empty() &&
+        event.positions.len() == event.units.len() &&
+        event.positions.iter().all(|p| *p < polymer.len()) &&
+        event.positions.windows(2).all(|w| w[0] < w[1] && destroyed[w[0] + 1..w[1]].iter().all(|d| *d)) &&
+        event.positions.iter().zip(event.units.iter())
+          .all(|(p, u)| !destroyed[*p] && polymer[*p] == *u);
+
+      if !valid {
+        return Err(event.step);
+      }
+
+      for position in event.positions.iter() {
+        destroyed[*position] = true;
+      }
     }
 
-    polymer = result;
+    Ok(())
   }
 }
 
-#[aoc(day5, part2)]
-pub fn part2(input: &Vec<Unit>) -> usize {
-  let mut best_result = input.len();
+/// Like `reduce_with`, but also records every reaction as it happens.
+pub fn reduce_traced<R: ReactionRules>(polymer: &[Unit], rules: &R) -> (Vec<Unit>, Trace) {
+  let mut stack: Vec<Unit> = Vec::with_capacity(polymer.len());
+  let mut positions: Vec<usize> = Vec::with_capacity(polymer.len());
+  let mut trace = Trace::default();
 
-  for letter in "abcdefghijklmnopqrstuvwxyz".chars() {
-    let filtered = input.into_iter()
-      .filter(|a| a.letter() != letter)
-      .map(|x| *x)
-      .collect::<Vec<Unit>>();
+  for (position, unit) in polymer.iter().enumerate() {
+    stack.push(*unit);
+    positions.push(position);
+
+    if let Some(length) = rules.reaction(&stack) {
+      let start = stack.len() - length;
+
+      trace.events.push(ReactionEvent {
+        step: trace.events.len(),
+        trigger: position,
+        positions: positions.split_off(start),
+        units: stack.split_off(start)
+      });
+    }
+  }
+
+  (stack, trace)
+}
+
+#[aoc(day5, part1)]
+pub fn part1(input: &[Unit]) -> usize {
+  reduce(input).len()
+}
+
+/// What's left of a polymer after removing every unit of some set of types and reacting it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Removal {
+  pub letters: Vec<char>,
+  pub length: usize
+}
+
+/// Every way of choosing `size` of `letters`, each in the order the letters were given.
+pub fn combinations(letters: &[char], size: usize) -> Vec<Vec<char>> {
+  if size == 0 {
+    return vec![Vec::new()];
+  }
 
-    let result = part1(&filtered);
+  let mut result = Vec::new();
 
-    if result < best_result {
-      best_result = result;
+  for (index, letter) in letters.iter().enumerate() {
+    for mut rest in combinations(&letters[index + 1..], size - 1) {
+      rest.insert(0, *letter);
+      result.push(rest);
     }
   }
 
-  best_result
+  result
+}
+
+/// Tries removing each of `candidates` from the polymer, spreading the work over `threads` threads,
+/// and returns the results from shortest to longest polymer. Ties keep the order of `candidates`.
+pub fn removal_ranking(polymer: &[Unit], candidates: &[Vec<char>], threads: usize) -> Vec<Removal> {
+  // removing a unit type can only add reactions, never undo ones that already happened, so we can
+  // start each search from the reduced polymer instead of the original one.
+  let reduced = reduce(polymer);
+  let chunk_size = candidates.len().div_ceil(threads.max(1)).max(1);
+
+  let mut ranking = std::thread::scope(|scope| {
+    let workers = candidates.chunks(chunk_size)
+      .map(|chunk| {
+        let reduced = &reduced;
+
+        scope.spawn(move || {
+          chunk.iter()
+            .map(|letters| {
+              let filtered = reduced.iter()
+                .filter(|a| !letters.contains(&a.letter()))
+                .copied()
+                .collect::<Vec<Unit>>();
+
+              Removal { letters: letters.clone(), length: reduce(&filtered).len() }
+            })
+            .collect::<Vec<Removal>>()
+        })
+      })
+      .collect::<Vec<_>>();
+
+    workers.into_iter()
+      .flat_map(|worker| worker.join().expect("removal worker panicked"))
+      .collect::<Vec<Removal>>()
+  });
+
+  ranking.sort_by_key(|removal| removal.length);
+  ranking
+}
+
+pub fn available_threads() -> usize {
+  std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+#[aoc(day5, part2)]
+pub fn part2(input: &[Unit]) -> usize {
+  let mut letters = input.iter().map(|u| u.letter()).collect::<Vec<char>>();
+  letters.sort();
+  letters.dedup();
+
+  let ranking = removal_ranking(input, &combinations(&letters, 1), available_threads());
+
+  // with no units there's nothing to remove, so the best is the polymer as it stands.
+  ranking.first().map_or_else(|| reduce(input).len(), |removal| removal.length)
 }
 
 #[cfg(test)]
@@ -100,6 +488,38 @@ mod tests {
     assert_eq!('a', Unit('A').letter());
   }
 
+  #[test]
+  pub fn unicode_letter_test() {
+    assert_eq!('é', Unit('É').letter());
+    assert_eq!('ß', Unit('ẞ').letter());
+    assert_eq!('k', Unit('\u{212A}').letter());
+    assert_eq!('İ', Unit('İ').letter());
+  }
+
+  #[test]
+  pub fn unicode_reacts_with_test() {
+    assert!(Unit('é').reacts_with(&Unit('É')));
+    assert!(Unit('ẞ').reacts_with(&Unit('ß')));
+    assert!(Unit('\u{212A}').reacts_with(&Unit('k')));
+    assert!(Unit('σ').reacts_with(&Unit('Σ')));
+
+    assert!(!Unit('ς').reacts_with(&Unit('Σ')));
+    assert!(!Unit('\u{212A}').reacts_with(&Unit('K')));
+    assert!(!Unit('İ').reacts_with(&Unit('i')));
+  }
+
+  #[test]
+  pub fn polymer_parser_test() {
+    assert_eq!(vec![Unit('a'), Unit('B'), Unit('ß'), Unit('ẞ')], polymer_parser(" aB\n ß ẞ\n").unwrap());
+    assert_eq!(polymer_parser("").unwrap(), reduce(&polymer_parser("aÉéA").unwrap()));
+
+    let error = polymer_parser("abc\nab1c").unwrap_err();
+    assert_eq!((2, 3, "1"), (error.line, error.column, error.text.as_str()));
+
+    let error = polymer_parser("Σ中").unwrap_err();
+    assert_eq!((1, 2, "中"), (error.line, error.column, error.text.as_str()));
+  }
+
   #[test]
   pub fn reacts_with_test() {
     assert!(Unit('a').reacts_with(&Unit('A')));
@@ -111,8 +531,166 @@ mod tests {
 
   }
 
+  #[test]
+  pub fn reduce_test() {
+    assert_eq!(polymer_parser("dabCBAcaDA").unwrap(), reduce(&polymer_parser("dabAcCaCBAcCcaDA").unwrap()));
+    assert_eq!(polymer_parser("").unwrap(), reduce(&polymer_parser("abBA").unwrap()));
+    assert_eq!(polymer_parser("abAB").unwrap(), reduce(&polymer_parser("abAB").unwrap()));
+    assert_eq!(polymer_parser("aabAAB").unwrap(), reduce(&polymer_parser("aabAAB").unwrap()));
+  }
+
+  #[test]
+  pub fn rule_table_test() {
+    let rules = RuleTable::parse("# opposite charges cancel\n+-\n-+\n\n  xyz  \n").unwrap();
+
+    assert_eq!(unit_parser("++"), reduce_with(&unit_parser("+-++-+"), &rules));
+    assert_eq!(unit_parser("aA"), reduce_with(&unit_parser("axyzA"), &rules));
+    assert_eq!(unit_parser(""), reduce_with(&unit_parser("xy+-z"), &rules));
+    assert_eq!(unit_parser("xyyz"), reduce_with(&unit_parser("xyyz"), &rules));
+
+    let error = RuleTable::parse("aA\n a A").unwrap_err();
+    assert_eq!((2, 2), (error.line, error.column));
+  }
+
+  #[test]
+  pub fn rule_table_matches_case_rule_test() {
+    let mut rules = RuleTable::new();
+
+    for (lower, upper) in "abcd".chars().zip("ABCD".chars()) {
+      rules.add(&[lower, upper]);
+      rules.add(&[upper, lower]);
+    }
+
+    let polymer = unit_parser("dabAcCaCBAcCcaDA");
+    assert_eq!(reduce(&polymer), reduce_with(&polymer, &rules));
+  }
+
+  #[test]
+  pub fn streaming_reducer_test() {
+    let mut reducer = StreamingReducer::new(&CaseRule);
+
+    assert_eq!(Ok(()), reducer.push_chars("dabAcC".chars()));
+    assert_eq!((6, 4), (reducer.consumed(), reducer.len()));
+
+    assert_eq!(Ok(()), reducer.push_chars("aCBAc\nCcaDA\n".chars()));
+    assert_eq!((16, 10), (reducer.consumed(), reducer.len()));
+    assert_eq!(reduce(&polymer_parser("dabAcCaCBAcCcaDA").unwrap()), reducer.into_units());
+
+    // the case rule rejects caseless characters just like `polymer_parser`, other rules don't.
+    let mut reducer = StreamingReducer::new(&CaseRule);
+    assert_eq!(Err(Unit('1')), reducer.push_chars("aB1b".chars()));
+    assert_eq!(2, reducer.consumed());
+
+    let rules = RuleTable::parse("12").unwrap();
+    let mut reducer = StreamingReducer::new(&rules);
+    assert_eq!(Ok(()), reducer.push_chars("a12b".chars()));
+    assert_eq!(unit_parser("ab"), reducer.into_units());
+  }
+
+  #[test]
+  pub fn streaming_reader_test() {
+    // long enough to span several chunks, with multi-byte characters landing on chunk boundaries.
+    let polymer = "aéÉbßẞ".repeat(30_000) + "xY";
+    let mut reducer = StreamingReducer::new(&CaseRule);
+    let mut lengths = Vec::new();
+
+    reducer.read_from(polymer.as_bytes(), |r| lengths.push(r.len())).unwrap();
+
+    assert!(lengths.len() > 1);
+    assert_eq!(unit_parser(&("ab".repeat(30_000) + "xY")), reducer.into_units());
+
+    let mut reducer = StreamingReducer::new(&CaseRule);
+    assert!(reducer.read_from(&[b'a', 0xff, b'b'][..], |_| {}).is_err());
+    assert!(reducer.read_from(&"aé".as_bytes()[..2], |_| {}).is_err());
+    assert!(StreamingReducer::new(&CaseRule).read_from(&b"aA7"[..], |_| {}).is_err());
+  }
+
+  #[test]
+  pub fn trace_test() {
+    let polymer = polymer_parser("dabAcCaCBAcCcaDA").unwrap();
+    let (reduced, trace) = reduce_traced(&polymer, &CaseRule);
+
+    assert_eq!(reduce(&polymer), reduced);
+    assert_eq!(3, trace.events.len());
+    assert_eq!(ReactionEvent {
+      step: 0,
+      trigger: 5,
+      positions: vec![4, 5],
+      units: polymer_parser("cC").unwrap()
+    }, trace.events[0]);
+    assert_eq!(ReactionEvent {
+      step: 1,
+      trigger: 6,
+      positions: vec![3, 6],
+      units: polymer_parser("Aa").unwrap()
+    }, trace.events[1]);
+
+    assert_eq!(polymer, trace.replay(&polymer, 0));
+    assert_eq!(polymer_parser("dabAaCBAcCcaDA").unwrap(), trace.replay(&polymer, 1));
+    assert_eq!(polymer_parser("dabCBAcCcaDA").unwrap(), trace.replay(&polymer, 2));
+    assert_eq!(reduced, trace.replay(&polymer, trace.events.len()));
+
+    assert_eq!(Ok(()), trace.verify(&polymer));
+    assert_eq!("step\ttrigger\tpositions\tunits\n0\t5\t4 5\tcC\n1\t6\t3 6\tAa\n2\t11\t10 11\tcC\n", trace.export());
+  }
+
+  #[test]
+  pub fn trace_verify_test() {
+    let polymer = polymer_parser("dabAcCaCBAcCcaDA").unwrap();
+    let (_, mut trace) = reduce_traced(&polymer, &CaseRule);
+
+    // pretend a buggy reducer let A and a react before cC was gone from between them.
+    trace.events.swap(0, 1);
+    trace.events[0].step = 0;
+    trace.events[1].step = 1;
+
+    assert_eq!(Err(0), trace.verify(&polymer));
+
+    // a trace recorded against a longer polymer points past the end of this one.
+    let (_, mut trace) = reduce_traced(&polymer, &CaseRule);
+    trace.events[0].positions = vec![4, 20];
+
+    assert_eq!(Err(0), trace.verify(&polymer));
+    assert_eq!(Err(2), reduce_traced(&polymer, &CaseRule).1.verify(&polymer[..11]));
+    assert_eq!(polymer_parser("dabACaCBAcCcaDA").unwrap(), trace.replay(&polymer, 1));
+  }
+
   #[test]
   pub fn part1_test() {
-    assert_eq!(10, part1(&polymer_parser("dabAcCaCBAcCcaDA")));
+    assert_eq!(10, part1(&polymer_parser("dabAcCaCBAcCcaDA").unwrap()));
+  }
+
+  #[test]
+  pub fn combinations_test() {
+    assert_eq!(vec![vec!['a'], vec!['b'], vec!['c']], combinations(&['a', 'b', 'c'], 1));
+    assert_eq!(vec![vec!['a', 'b'], vec!['a', 'c'], vec!['b', 'c']], combinations(&['a', 'b', 'c'], 2));
+    assert_eq!(vec![vec!['a', 'b', 'c']], combinations(&['a', 'b', 'c'], 3));
+    assert!(combinations(&['a', 'b'], 3).is_empty());
+  }
+
+  #[test]
+  pub fn removal_ranking_test() {
+    let polymer = polymer_parser("dabAcCaCBAcCcaDA").unwrap();
+
+    let singles = removal_ranking(&polymer, &combinations(&['a', 'b', 'c', 'd'], 1), 3);
+    assert_eq!(vec![
+      Removal { letters: vec!['c'], length: 4 },
+      Removal { letters: vec!['a'], length: 6 },
+      Removal { letters: vec!['d'], length: 6 },
+      Removal { letters: vec!['b'], length: 8 },
+    ], singles);
+
+    let pairs = removal_ranking(&polymer, &combinations(&['a', 'b', 'c', 'd'], 2), 4);
+    assert_eq!(6, pairs.len());
+    assert_eq!(Removal { letters: vec!['a', 'b'], length: 0 }, pairs[0]);
+    assert_eq!(pairs, removal_ranking(&polymer, &combinations(&['a', 'b', 'c', 'd'], 2), 1));
+  }
+
+  #[test]
+  pub fn part2_test() {
+    assert_eq!(4, part2(&polymer_parser("dabAcCaCBAcCcaDA").unwrap()));
+    assert_eq!(0, part2(&polymer_parser("éüÉ").unwrap()));
+    assert_eq!(0, part2(&polymer_parser("").unwrap()));
+    assert_eq!(0, part2(&polymer_parser(" \n").unwrap()));
   }
 }
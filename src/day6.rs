@@ -1,176 +1,1555 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::BinaryHeap;
+use std::cmp::Reverse;
+use crate::image::{Image, Rgb};
+use crate::parse::ParseError;
 
-#[derive(Copy, Clone, Debug)]
-pub struct Bounds {
-  min_x: i16,
-  max_x: i16,
-  min_y: i16,
-  max_y: i16
+/// Bounds in any number of dimensions, covering `min[axis]..=max[axis]` along each axis.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Extent<const N: usize> {
+  pub min: [i64; N],
+  pub max: [i64; N]
+}
+
+/// A rectangle of cells, with x as the first axis and y as the second.
+pub type Bounds = Extent<2>;
+
+impl<const N: usize> Extent<N> {
+  /// The smallest extent holding every point, or `None` if there aren't any.
+  pub fn around(points: &[[i64; N]]) -> Option<Extent<N>> {
+    let first = points.first()?;
+    let mut extent = Extent { min: *first, max: *first };
+
+    for point in points {
+      for (axis, position) in point.iter().enumerate() {
+        extent.min[axis] = extent.min[axis].min(*position);
+        extent.max[axis] = extent.max[axis].max(*position);
+      }
+    }
+
+    Some(extent)
+  }
+
+  pub fn len(&self, axis: usize) -> usize {
+    (self.max[axis] - self.min[axis]) as usize + 1
+  }
+
+  pub fn volume(&self) -> usize {
+    (0..N).map(|axis| self.len(axis)).product()
+  }
+
+  pub fn contains(&self, point: &[i64; N]) -> bool {
+    (0..N).all(|axis| point[axis] >= self.min[axis] && point[axis] <= self.max[axis])
+  }
+
+  pub fn on_edge(&self, point: &[i64; N]) -> bool {
+    (0..N).any(|axis| point[axis] == self.min[axis] || point[axis] == self.max[axis])
+  }
+
+  /// This extent with `margin` more cells on every side.
+  pub fn widened(&self, margin: i64) -> Extent<N> {
+    let mut wide = *self;
+
+    for axis in 0..N {
+      wide.min[axis] -= margin;
+      wide.max[axis] += margin;
+    }
+
+    wide
+  }
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct Coord {
-  x: i16,
-  y: i16,
-  name: char
+  x: i64,
+  y: i64,
+  /// Where the coordinate came in the input, counting from 0.
+  id: usize,
+  /// A single character to show the coordinate by when drawing it, if it has one.
+  label: Option<char>
+}
+
+// The first coordinates are labelled like this, and any more go without.
+const LABELS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ1234567890abcdefghijklmnopqrstuvwxyz";
+
+impl Coord {
+  pub fn new(id: usize, x: i64, y: i64) -> Coord {
+    Coord { x, y, id, label: LABELS.chars().nth(id) }
+  }
+
+  pub fn x(&self) -> i64 {
+    self.x
+  }
+
+  pub fn y(&self) -> i64 {
+    self.y
+  }
+
+  /// The coordinate as a point for the N-dimensional code.
+  pub fn position(&self) -> [i64; 2] {
+    [self.x, self.y]
+  }
+
+  pub fn id(&self) -> usize {
+    self.id
+  }
+
+  pub fn label(&self) -> Option<char> {
+    self.label
+  }
+
+  pub fn with_label(self, label: Option<char>) -> Coord {
+    Coord { label, ..self }
+  }
+
+  /// The coordinate's label, or `#` and its ID if it doesn't have one.
+  pub fn display_name(&self) -> String {
+    match self.label {
+      Some(label) => label.to_string(),
+      None => format!("#{}", self.id)
+    }
+  }
 }
 
 #[aoc_generator(day6)]
-pub fn coordinate_parser(input: &str) -> Vec<Coord> {
+pub fn coordinate_parser(input: &str) -> Result<Vec<Coord>, ParseError> {
   let mut coords = Vec::new();
 
-
   for (index, line) in input.lines().enumerate() {
-    let parts: Vec<&str> = line.split(", ").collect();
+    let separator = line.find(", ")
+      .ok_or_else(|| ParseError::line(index, line, "a coordinate like \"<x>, <y>\""))?;
+
+    let (x_text, y_text) = (&line[..separator], &line[separator + 2..]);
+
+    let x = x_text.parse()
+      .map_err(|_| ParseError::new(index, line, 0, x_text, "an integer"))?;
+    let y = y_text.parse()
+      .map_err(|_| ParseError::new(index, line, separator + 2, y_text, "an integer"))?;
+
+    coords.push(Coord::new(index, x, y));
+  }
+
+  Ok(coords)
+}
+
+fn positions(coords: &[Coord]) -> Vec<[i64; 2]> {
+  coords.iter().map(|coord| coord.position()).collect()
+}
+
+/// The smallest bounds holding every coordinate, or `None` if there aren't any.
+fn bounds_for_coords(coords: &[Coord]) -> Option<Bounds> {
+  Extent::around(&positions(coords))
+}
+
+/// Which coordinate is closest to a cell.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Owner {
+  Coord(usize),
+  /// More than one coordinate is closest.
+  Tie
+}
+
+/// The nearest point for every cell in an extent. Cells are stored with the first axis changing
+/// fastest, so in two dimensions that's row by row.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Lattice<const N: usize> {
+  extent: Extent<N>,
+  cells: Vec<Owner>
+}
+
+/// The nearest coordinate for every cell in some bounds.
+pub type Partition = Lattice<2>;
+
+impl<const N: usize> Lattice<N> {
+  pub fn extent(&self) -> Extent<N> {
+    self.extent
+  }
+
+  fn index(&self, point: &[i64; N]) -> usize {
+    let mut index = 0;
+
+    for axis in (0..N).rev() {
+      index = index * self.extent.len(axis) + (point[axis] - self.extent.min[axis]) as usize;
+    }
+
+    index
+  }
+
+  fn point(&self, mut index: usize) -> [i64; N] {
+    let mut point = self.extent.min;
+
+    for (axis, position) in point.iter_mut().enumerate() {
+      *position += (index % self.extent.len(axis)) as i64;
+      index /= self.extent.len(axis);
+    }
+
+    point
+  }
+
+  pub fn owner(&self, point: &[i64; N]) -> Owner {
+    self.cells[self.index(point)]
+  }
+
+  /// Every cell along with its owner.
+  pub fn iter(&self) -> impl Iterator<Item=([i64; N], Owner)> + '_ {
+    self.cells.iter().enumerate().map(move |(index, owner)| (self.point(index), *owner))
+  }
+}
+
+/// A way of measuring the distance between two cells, given how far apart they are along each
+/// axis. Metrics are two-dimensional unless they say otherwise.
+pub trait Metric<const N: usize = 2> {
+  fn distance(&self, offset: [i64; N]) -> i64;
+
+  /// If this metric's distances are the costs of the cheapest paths through the grid, the moves
+  /// those paths are made of, as an offset and a cost above zero. `nearest_points` can flood fill
+  /// under metrics like this instead of measuring every distance.
+  fn steps(&self) -> Option<Vec<([i64; N], i64)>> {
+    None
+  }
+
+  /// How far past `extent`, the smallest extent holding every point, the regions settle down: from
+  /// there on, each cell on the edge has the same owner as every cell straight out from it, so any
+  /// region reaching the edge goes on forever. `None` means there's no such margin, and
+  /// `region_volumes` treats the metric like straight-line distance instead.
+  fn settled_margin(&self, _extent: &Extent<N>) -> Option<i64> {
+    None
+  }
+}
+
+/// The puzzle's taxicab distance, in any number of dimensions.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Manhattan;
+
+impl<const N: usize> Metric<N> for Manhattan {
+  fn distance(&self, offset: [i64; N]) -> i64 {
+    offset.iter().map(|delta| delta.abs()).sum()
+  }
+
+  fn steps(&self) -> Option<Vec<([i64; N], i64)>> {
+    let mut steps = Vec::new();
+
+    for axis in 0..N {
+      for delta in [-1, 1].iter() {
+        let mut offset = [0; N];
+        offset[axis] = *delta;
+        steps.push((offset, 1));
+      }
+    }
+
+    Some(steps)
+  }
+
+  // Every step away from the extent adds one to the distance to every point.
+  fn settled_margin(&self, _extent: &Extent<N>) -> Option<i64> {
+    Some(0)
+  }
+}
+
+/// Distance for a king on a chessboard, where diagonal moves cost the same as straight ones.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Chebyshev;
+
+impl<const N: usize> Metric<N> for Chebyshev {
+  fn distance(&self, offset: [i64; N]) -> i64 {
+    offset.iter().map(|delta| delta.abs()).max().unwrap_or(0)
+  }
+
+  fn steps(&self) -> Option<Vec<([i64; N], i64)>> {
+    let neighbours = 3usize.pow(N as u32);
+
+    let steps = (0..neighbours)
+      .filter(|neighbour| *neighbour != neighbours / 2)
+      .map(|mut neighbour| {
+        let mut offset = [0; N];
+        for delta in offset.iter_mut() {
+          *delta = (neighbour % 3) as i64 - 1;
+          neighbour /= 3;
+        }
+        (offset, 1)
+      })
+      .collect();
+
+    Some(steps)
+  }
+
+  // Diagonal steps out from the corners add one to every distance straight away, but straight
+  // steps only do once they're far enough out that the distance across the extent doesn't matter.
+  fn settled_margin(&self, extent: &Extent<N>) -> Option<i64> {
+    (0..N).map(|axis| extent.max[axis] - extent.min[axis]).max()
+  }
+}
+
+/// Straight-line distance, squared so it stays an integer. Squaring doesn't change which coordinate
+/// is nearest, but it does change total distances.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct EuclideanSquared;
+
+impl<const N: usize> Metric<N> for EuclideanSquared {
+  fn distance(&self, offset: [i64; N]) -> i64 {
+    offset.iter().map(|delta| delta * delta).sum()
+  }
+}
+
+/// Manhattan distance where moving along each axis has its own cost.
+#[derive(Copy, Clone, Debug)]
+pub struct Weighted {
+  pub x: i64,
+  pub y: i64
+}
+
+impl Metric for Weighted {
+  fn distance(&self, [dx, dy]: [i64; 2]) -> i64 {
+    self.x * dx.abs() + self.y * dy.abs()
+  }
+
+  fn steps(&self) -> Option<Vec<([i64; 2], i64)>> {
+    if self.x > 0 && self.y > 0 {
+      Some(vec![([-1, 0], self.x), ([1, 0], self.x), ([0, -1], self.y), ([0, 1], self.y)])
+    } else {
+      None
+    }
+  }
 
-    let x = parts.get(0).unwrap().parse().unwrap();
-    let y = parts.get(1).unwrap().parse().unwrap();
-    let name = "ABCDEFGHIJKLMNOPQRSTUVWXYZ1234567890abcdefghijklmnopqrstuvwxyz".chars().nth(index).unwrap();
+  // Like `Manhattan`, every step away adds the same amount to every distance.
+  fn settled_margin(&self, _extent: &Bounds) -> Option<i64> {
+    Some(0)
+  }
+}
 
-    coords.push(Coord { x, y, name });
+fn distance_between<M: Metric<N> + ?Sized, const N: usize>(metric: &M, a: &[i64; N], b: &[i64; N]) -> i64 {
+  let mut offset = [0; N];
+
+  for axis in 0..N {
+    offset[axis] = a[axis] - b[axis];
   }
 
-  coords
+  metric.distance(offset)
 }
 
-fn bounds_for_coords(coords: &Vec<Coord>) -> Bounds {
-  let mut bounds = Bounds {
-    min_x: 0,
-    max_x: 0,
-    min_y: 0,
-    max_y: 0
+/// Labels every cell in `extent` with its nearest point. Every point has to be inside the extent.
+/// Under metrics made of grid steps this floods out from all of the points at once, cheapest cells
+/// first: a cell's owner is decided by the neighbours its cheapest paths come through, and if they
+/// disagree, or any of them is already a tie, so is the cell. Other metrics fall back to
+/// `nearest_points_brute_force`.
+pub fn nearest_points<M: Metric<N> + ?Sized, const N: usize>(points: &[[i64; N]], extent: Extent<N>, metric: &M) -> Lattice<N> {
+  let steps = match metric.steps() {
+    Some(steps) => steps,
+    None => return nearest_points_brute_force(points, extent, metric)
   };
 
-  for coord in coords {
-    if coord.x < bounds.min_x {
-      bounds.min_x = coord.x;
+  let mut lattice = Lattice { extent, cells: vec![Owner::Tie; extent.volume()] };
+  let mut distance = vec![i64::MAX; lattice.cells.len()];
+  let mut queue = BinaryHeap::new();
+
+  for (index, point) in points.iter().enumerate() {
+    let cell = lattice.index(point);
+
+    if distance[cell] == 0 {
+      lattice.cells[cell] = Owner::Tie;
+    } else {
+      distance[cell] = 0;
+      lattice.cells[cell] = Owner::Coord(index);
+      queue.push(Reverse((0, cell)));
     }
+  }
+
+  while let Some(Reverse((cell_distance, cell))) = queue.pop() {
+    if cell_distance > distance[cell] {
+      continue;
+    }
+
+    let point = lattice.point(cell);
+    let owner = lattice.cells[cell];
+
+    for (offset, cost) in steps.iter() {
+      let mut next = point;
+      for axis in 0..N {
+        next[axis] += offset[axis];
+      }
+
+      if !extent.contains(&next) {
+        continue;
+      }
+
+      let neighbour = lattice.index(&next);
+      let next_distance = cell_distance + cost;
 
-    if coord.x > bounds.max_x {
-      bounds.max_x = coord.x;
+      if next_distance < distance[neighbour] {
+        distance[neighbour] = next_distance;
+        lattice.cells[neighbour] = owner;
+        queue.push(Reverse((next_distance, neighbour)));
+
+      } else if next_distance == distance[neighbour] && lattice.cells[neighbour] != owner {
+        lattice.cells[neighbour] = Owner::Tie;
+      }
     }
+  }
+
+  lattice
+}
+
+/// The original way of finding the nearest points: measure the distance from every cell to every
+/// point. It's much slower, but obviously right, so tests check the flood fill against it.
+pub fn nearest_points_brute_force<M: Metric<N> + ?Sized, const N: usize>(points: &[[i64; N]], extent: Extent<N>, metric: &M) -> Lattice<N> {
+  let mut lattice = Lattice { extent, cells: vec![Owner::Tie; extent.volume()] };
+
+  let mut position = extent.min;
+
+  for cell in lattice.cells.iter_mut() {
+    let mut best_distance = i64::MAX;
 
-    if coord.y < bounds.min_y {
-      bounds.min_y = coord.y;
+    for (index, point) in points.iter().enumerate() {
+      let dist = distance_between(metric, &position, point);
+
+      if dist < best_distance {
+        best_distance = dist;
+        *cell = Owner::Coord(index);
+      } else if dist == best_distance {
+        *cell = Owner::Tie;
+      }
     }
 
-    if coord.y > bounds.max_y {
-      bounds.max_y = coord.y;
+    // on to the next cell, first axis fastest
+    for (axis, position) in position.iter_mut().enumerate() {
+      if *position < extent.max[axis] {
+        *position += 1;
+        break;
+      }
+
+      *position = extent.min[axis];
     }
   }
 
-  bounds.min_x -= 1;
-  bounds.min_y -= 1;
-  bounds.max_x += 1;
-  bounds.max_y += 1;
+  lattice
+}
 
-  bounds
+/// `nearest_points` for the puzzle's coordinates.
+pub fn nearest_coords<M: Metric + ?Sized>(coords: &[Coord], bounds: Bounds, metric: &M) -> Partition {
+  nearest_points(&positions(coords), bounds, metric)
 }
-#[aoc(day6, part1)]
-pub fn part1(coords: &Vec<Coord>) -> u16 {
 
-  let bounds = bounds_for_coords(coords);
+/// `nearest_points_brute_force` for the puzzle's coordinates.
+pub fn nearest_coords_brute_force<M: Metric + ?Sized>(coords: &[Coord], bounds: Bounds, metric: &M) -> Partition {
+  nearest_points_brute_force(&positions(coords), bounds, metric)
+}
 
-  let mut region_size = HashMap::new();
-  let mut infinite_regions = HashSet::new();
+fn cross(o: &[i64; 2], a: &[i64; 2], b: &[i64; 2]) -> i64 {
+  (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+}
+
+/// The indices of the corners of the points' convex hull, anticlockwise when y points up,
+/// starting from the lowest x. Points in the middle of an edge aren't corners, and of points
+/// sharing a position only the first can be one.
+pub fn convex_hull(points: &[[i64; 2]]) -> Vec<usize> {
+  let mut order = (0..points.len()).collect::<Vec<usize>>();
+  order.sort_by_key(|index| (points[*index], *index));
+  order.dedup_by_key(|index| points[*index]);
+
+  if order.len() < 3 {
+    return order;
+  }
 
-  for y in bounds.min_y..bounds.max_y {
-    for x in bounds.min_x..bounds.max_x {
-      let mut distances = HashMap::new();
+  let mut hull: Vec<usize> = Vec::new();
 
-      for (index, coord) in coords.iter().enumerate() {
-        let dist = num::abs(x - coord.x) + num::abs(y - coord.y);
+  for pass in 0..2 {
+    let start = hull.len();
 
-        distances.entry(dist)
-          .or_insert(Vec::new())
-          .push(index);
+    for &index in order.iter() {
+      while hull.len() >= start + 2
+        && cross(&points[hull[hull.len() - 2]], &points[hull[hull.len() - 1]], &points[index]) <= 0 {
+        hull.pop();
       }
 
-      let best_distance = distances.keys().min().unwrap();
-      let best_regions = distances.get(best_distance).unwrap();
+      hull.push(index);
+    }
+
+    // the last corner of each half is the first of the other
+    hull.pop();
+
+    if pass == 0 {
+      order.reverse();
+    }
+  }
+
+  hull
+}
+
+/// Whether a point is strictly inside the polygon made by the hull's corners. Points on its edges
+/// aren't.
+fn inside_hull(points: &[[i64; 2]], hull: &[usize], point: &[i64; 2]) -> bool {
+  hull.len() >= 3 && (0..hull.len())
+    .all(|i| cross(&points[hull[i]], &points[hull[(i + 1) % hull.len()]], point) > 0)
+}
+
+/// Under straight-line distance, the points whose regions go on forever: those on the boundary of
+/// the convex hull that don't share their position with another point.
+fn unbounded_by_hull(points: &[[i64; 2]]) -> HashSet<usize> {
+  let hull = convex_hull(points);
+
+  let mut sharing = HashMap::new();
+  for point in points {
+    *sharing.entry(*point).or_insert(0) += 1;
+  }
+
+  (0..points.len())
+    .filter(|index| sharing[&points[*index]] == 1 && !inside_hull(points, &hull, &points[*index]))
+    .collect()
+}
+
+/// Everything `region_volumes` works out about the points' regions.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RegionVolumes<const N: usize> {
+  /// The smallest extent holding every point.
+  pub extent: Extent<N>,
+  /// The extent the regions were measured in. Every finite region is inside it whole: under a
+  /// metric with a `settled_margin` because regions reaching its edge go on forever, and otherwise
+  /// because it holds the Voronoi cell of every point with a finite region.
+  pub searched: Extent<N>,
+  /// How many cells inside `searched` each point is nearest to. For points with finite regions,
+  /// that's the whole region.
+  pub volumes: Vec<usize>,
+  /// How many cells inside `searched` are as near to one point as another.
+  pub ties: usize,
+  /// The points whose regions go on forever, in order.
+  pub infinite: Vec<usize>
+}
+
+/// Everything `part1` works out on the way to its answer.
+pub type Diagnostics = RegionVolumes<2>;
+
+impl<const N: usize> RegionVolumes<N> {
+  /// The point with the biggest finite region, and that region's volume. If two regions are just
+  /// as big, the first point is picked.
+  pub fn largest_finite(&self) -> Option<(usize, usize)> {
+    self.volumes.iter().cloned().enumerate()
+      .filter(|(index, _)| self.infinite.binary_search(index).is_err())
+      .fold(None, |best, (index, volume)| match best {
+        Some((_, best_volume)) if best_volume >= volume => best,
+        _ => Some((index, volume))
+      })
+  }
+}
+
+/// The part of a convex polygon where `a·q <= c`.
+fn clip(polygon: &[[f64; 2]], a: [f64; 2], c: f64) -> Vec<[f64; 2]> {
+  let side = |q: &[f64; 2]| a[0] * q[0] + a[1] * q[1] - c;
+  let mut clipped = Vec::new();
+
+  for (i, here) in polygon.iter().enumerate() {
+    let next = &polygon[(i + 1) % polygon.len()];
+    let (here_side, next_side) = (side(here), side(next));
+
+    if here_side <= 0.0 {
+      clipped.push(*here);
+    }
+
+    if (here_side < 0.0 && next_side > 0.0) || (here_side > 0.0 && next_side < 0.0) {
+      let t = here_side / (here_side - next_side);
+      clipped.push([here[0] + t * (next[0] - here[0]), here[1] + t * (next[1] - here[1])]);
+    }
+  }
+
+  clipped
+}
+
+/// Under straight-line distance, the smallest bounds holding the regions of the points at
+/// `indices`, which have to be strictly inside the convex hull, or `None` if none of them have a
+/// region. Each region is the point's Voronoi cell: a square around the point that's big enough to
+/// hold the cell, cut down by the point's bisector with every other point. The corners are worked
+/// out in floating point, so the bounds get a cell of slack on every side.
+fn voronoi_bounds(points: &[[i64; 2]], indices: &[usize]) -> Option<Bounds> {
+  let hull = convex_hull(points);
+  let mut corners = Vec::new();
+
+  for &index in indices {
+    let point = points[index];
+
+    // points sharing a position tie for every cell
+    if points.iter().enumerate().any(|(other, position)| other != index && *position == point) {
+      continue;
+    }
+
+    // The hull holds a disk of radius `inset` around the point, so in every direction some corner
+    // of the hull is at least `inset` further along than the point is. That corner's bisector
+    // cuts the cell off within `furthest² / (2 * inset)`, where `furthest` is the distance to the
+    // furthest corner.
+    let mut inset = f64::INFINITY;
+    let mut furthest: f64 = 0.0;
+
+    for (i, corner) in hull.iter().enumerate() {
+      let (a, b) = (&points[*corner], &points[hull[(i + 1) % hull.len()]]);
+      let edge = ((b[0] - a[0]) as f64).hypot((b[1] - a[1]) as f64);
+
+      inset = inset.min(cross(a, b, &point) as f64 / edge);
+      furthest = furthest.max(((a[0] - point[0]) as f64).hypot((a[1] - point[1]) as f64));
+    }
+
+    let reach = furthest * furthest / (2.0 * inset) + 1.0;
+    let (x, y) = (point[0] as f64, point[1] as f64);
+    let mut cell = vec![[x - reach, y - reach], [x + reach, y - reach], [x + reach, y + reach], [x - reach, y + reach]];
+
+    // the cell is the side of each bisector nearer the point, where 2(other - point)·q is at most
+    // |other|² - |point|²
+    for other in points.iter().filter(|other| **other != point) {
+      let a = [2.0 * (other[0] - point[0]) as f64, 2.0 * (other[1] - point[1]) as f64];
+      let c = (other[0] * other[0] + other[1] * other[1] - point[0] * point[0] - point[1] * point[1]) as f64;
+
+      cell = clip(&cell, a, c);
+    }
+
+    corners.extend(cell);
+  }
+
+  let first = corners.first()?;
+  let mut bounds = [first[0], first[0], first[1], first[1]];
+
+  for corner in corners.iter() {
+    bounds = [bounds[0].min(corner[0]), bounds[1].max(corner[0]), bounds[2].min(corner[1]), bounds[3].max(corner[1])];
+  }
+
+  Some(Bounds {
+    min: [bounds[0].floor() as i64 - 1, bounds[2].floor() as i64 - 1],
+    max: [bounds[1].ceil() as i64 + 1, bounds[3].ceil() as i64 + 1]
+  })
+}
+
+/// Works out how big each point's region is, or `None` if there aren't any points.
+///
+/// Metrics with a `settled_margin` get their lattice widened by that margin, and a region is
+/// infinite exactly when it reaches the edge. Other metrics are assumed to behave like
+/// straight-line distance, where a region is infinite exactly when its point is on the boundary
+/// of the convex hull and doesn't share its position with another point. That's only worked out
+/// in two dimensions, so in any other this returns `None` for them. Finite regions can still
+/// reach well past the extent under those metrics, so the lattice is widened to hold their
+/// Voronoi cells.
+pub fn region_volumes<M: Metric<N> + ?Sized, const N: usize>(metric: &M, points: &[[i64; N]]) -> Option<RegionVolumes<N>> {
+  let extent = Extent::around(points)?;
+  let margin = metric.settled_margin(&extent);
+
+  let mut searched = extent.widened(margin.unwrap_or(0));
+  let mut lattice = nearest_points(points, searched, metric);
+
+  let infinite = match margin {
+    Some(_) => lattice.iter()
+      .filter_map(|(point, owner)| match owner {
+        Owner::Coord(index) if searched.on_edge(&point) => Some(index),
+        _ => None
+      })
+      .collect::<HashSet<usize>>(),
+    None if N == 2 => {
+      let plane = points.iter().map(|point| [point[0], point[1]]).collect::<Vec<[i64; 2]>>();
+      let infinite = unbounded_by_hull(&plane);
+      let finite = (0..points.len()).filter(|index| !infinite.contains(index)).collect::<Vec<usize>>();
+
+      if let Some(cells) = voronoi_bounds(&plane, &finite) {
+        for axis in 0..2 {
+          searched.min[axis] = searched.min[axis].min(cells.min[axis]);
+          searched.max[axis] = searched.max[axis].max(cells.max[axis]);
+        }
+
+        lattice = nearest_points(points, searched, metric);
+      }
+
+      infinite
+    },
+    None => return None
+  };
+
+  let mut volumes = vec![0; points.len()];
+  let mut ties = 0;
+
+  for (_, owner) in lattice.iter() {
+    match owner {
+      Owner::Coord(index) => volumes[index] += 1,
+      Owner::Tie => ties += 1
+    }
+  }
+
+  let mut infinite = infinite.into_iter().collect::<Vec<usize>>();
+  infinite.sort();
+
+  Some(RegionVolumes { extent, searched, volumes, ties, infinite })
+}
+
+/// `region_volumes` for the puzzle's coordinates.
+pub fn diagnose<M: Metric + ?Sized>(metric: &M, coords: &[Coord]) -> Option<Diagnostics> {
+  region_volumes(metric, &positions(coords))
+}
+
+#[aoc(day6, part1)]
+pub fn part1(coords: &[Coord]) -> usize {
+  part1_inner(&Manhattan, coords)
+}
+
+pub fn part1_inner<M: Metric + ?Sized>(metric: &M, coords: &[Coord]) -> usize {
+  diagnose(metric, coords)
+    .and_then(|diagnostics| diagnostics.largest_finite())
+    .map_or(0, |(_, size)| size)
+}
+
+/// Something about a safe region that means its size might not be the whole story.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RegionWarning {
+  /// Some of the region's cells are on the edge of the area that was searched, so the region
+  /// might carry on outside it.
+  TouchesSearchBounds { cells: usize }
+}
+
+/// The cells whose total distance to all of the coordinates is under a threshold.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SafeRegion {
+  /// The area that was searched.
+  pub search_bounds: Bounds,
+  pub cells: HashSet<(i64, i64)>,
+  pub warnings: Vec<RegionWarning>
+}
+
+impl SafeRegion {
+  pub fn len(&self) -> usize {
+    self.cells.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.cells.is_empty()
+  }
+
+  pub fn contains(&self, x: i64, y: i64) -> bool {
+    self.cells.contains(&(x, y))
+  }
+
+  /// The smallest bounds holding every cell in the region, or `None` if the region is empty.
+  pub fn bounding_box(&self) -> Option<Bounds> {
+    Extent::around(&self.cells.iter().map(|&(x, y)| [x, y]).collect::<Vec<[i64; 2]>>())
+  }
 
-      // one coordinate is closest
-      if best_regions.len() == 1 {
-        let best_index = *best_regions.first().unwrap();
-        let best_coord = *coords.get(best_index).unwrap();
-        // print!("{}", best_coord.name);
+  /// Whether every cell can be reached from every other by moving up, down, left and right
+  /// without leaving the region. An empty region counts as connected.
+  pub fn is_connected(&self) -> bool {
+    let start = match self.cells.iter().next() {
+      Some(&start) => start,
+      None => return true
+    };
 
-        *region_size.entry(best_index).or_insert(0) += 1;
+    let mut seen = HashSet::new();
+    let mut stack = vec![start];
+    seen.insert(start);
 
-        if x == bounds.min_x || x == bounds.max_x || y == bounds.min_y || y == bounds.max_y {
-          infinite_regions.insert(best_index);
+    while let Some((x, y)) = stack.pop() {
+      for &neighbour in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)].iter() {
+        if self.cells.contains(&neighbour) && seen.insert(neighbour) {
+          stack.push(neighbour);
         }
-      } else {
-        // print!("{}", ".");
       }
     }
-    // println!();
+
+    seen.len() == self.cells.len()
   }
+}
+
+/// Finds every cell in `search_bounds` whose total distance to the coordinates is under `threshold`.
+pub fn safe_region<M: Metric + ?Sized>(coords: &[Coord], search_bounds: Bounds, metric: &M, threshold: i64) -> SafeRegion {
+  let mut cells = HashSet::new();
+  let mut edge_cells = 0;
 
-  println!("Infinite regions: {:?}", infinite_regions);
-  println!("Region sizes: {:?}", region_size);
+  for y in search_bounds.min[1]..=search_bounds.max[1] {
+    for x in search_bounds.min[0]..=search_bounds.max[0] {
+      let total_distance: i64 = coords.iter()
+        .map(|coord| distance_between(metric, &[x, y], &coord.position()))
+        .sum();
 
-  let mut biggest_size = 0;
-  for (index, size) in region_size.iter() {
-    if !infinite_regions.contains(index) && *size > biggest_size {
-      biggest_size = *size;
+      if total_distance < threshold {
+        cells.insert((x, y));
+
+        if search_bounds.on_edge(&[x, y]) {
+          edge_cells += 1;
+        }
+      }
     }
   }
 
-  biggest_size
+  let mut warnings = Vec::new();
+  if edge_cells > 0 {
+    warnings.push(RegionWarning::TouchesSearchBounds { cells: edge_cells });
+  }
+
+  SafeRegion { search_bounds, cells, warnings }
 }
 
-#[aoc(day6, part2)]
-pub fn part2(coords: &Vec<Coord>) -> u16 {
-  let mut region_size = 0;
+/// What the renderers draw: the partition over the smallest bounds holding every coordinate, which
+/// regions are infinite and, optionally, the safe region.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Picture {
+  pub partition: Partition,
+  pub infinite: Vec<usize>,
+  pub region: Option<SafeRegion>
+}
 
-  let bounds = bounds_for_coords(coords);
+/// Works out a picture of the coordinates under a metric, with the safe region for `threshold`
+/// if there is one. Returns `None` if there aren't any coordinates.
+pub fn picture<M: Metric + ?Sized>(metric: &M, coords: &[Coord], threshold: Option<i64>) -> Option<Picture> {
+  let diagnostics = diagnose(metric, coords)?;
+  let bounds = diagnostics.extent;
 
-  for y in bounds.min_y..bounds.max_y {
-    for x in bounds.min_x..bounds.max_x {
+  Some(Picture {
+    partition: nearest_coords(coords, bounds, metric),
+    infinite: diagnostics.infinite,
+    region: threshold.map(|threshold| safe_region(coords, bounds, metric, threshold))
+  })
+}
 
-      let mut total_distance = 0;
+/// The character to draw a coordinate's cells with: its label in lowercase, as long as that can't
+/// be mistaken for a coordinate or for another coordinate's cells. Otherwise, `*`.
+fn cell_glyphs(coords: &[Coord]) -> Vec<char> {
+  let labels = coords.iter().filter_map(|coord| coord.label).collect::<HashSet<char>>();
 
-      for (index, coord) in coords.iter().enumerate() {
-        let dist = num::abs(x - coord.x) + num::abs(y - coord.y);
+  let mut lowercase = HashMap::new();
+  for label in labels.iter() {
+    *lowercase.entry(label.to_ascii_lowercase()).or_insert(0) += 1;
+  }
 
-        total_distance += dist;
-      }
+  coords.iter()
+    .map(|coord| match coord.label {
+      Some(label) => {
+        let glyph = label.to_ascii_lowercase();
+
+        if !labels.contains(&glyph) && lowercase[&glyph] == 1 { glyph } else { '*' }
+      },
+      None => '*'
+    })
+    .collect()
+}
+
+/// Draws a partition like the puzzle does: each coordinate is its label, the cells nearest to it
+/// are its label in lowercase and ties are `.`. Coordinates without labels are drawn as `*`, and so
+/// are cells whose lowercase label would be ambiguous, like those of digits or of `A` when there's
+/// also an `a`. Cells in the safe region, if there is one, are drawn as `#` instead. A last line
+/// lists the coordinates with infinite regions.
+pub fn render_text(coords: &[Coord], picture: &Picture) -> String {
+  let partition = &picture.partition;
+  let seeds = coords.iter()
+    .map(|coord| ((coord.x, coord.y), coord.label.unwrap_or('*')))
+    .collect::<HashMap<(i64, i64), char>>();
+  let glyphs = cell_glyphs(coords);
 
-      if total_distance < 10000 {
-        region_size += 1;
-        // print!("#");
-      } else {
-        // print!(".");
+  let mut out = String::new();
+
+  for ([x, y], owner) in partition.iter() {
+    let cell = if let Some(label) = seeds.get(&(x, y)) {
+      *label
+    } else if picture.region.as_ref().is_some_and(|region| region.contains(x, y)) {
+      '#'
+    } else {
+      match owner {
+        Owner::Coord(index) => glyphs[index],
+        Owner::Tie => '.'
       }
+    };
+
+    out.push(cell);
+
+    if x == partition.extent().max[0] {
+      out.push('\n');
     }
+  }
+
+  let names = picture.infinite.iter().map(|index| coords[*index].display_name()).collect::<Vec<String>>();
+  out.push_str(&format!("infinite: {}\n", if names.is_empty() { "none".to_string() } else { names.join(", ") }));
+
+  out
+}
+
+const TIE_COLOUR: Rgb = [64, 64, 64];
+const SEED_COLOUR: Rgb = [0, 0, 0];
+
+/// A colour for each coordinate's region, spreading the hues around the colour wheel so that
+/// neighbouring indices look different. Infinite regions get a darker shade.
+fn region_colour(index: usize, infinite: bool) -> Rgb {
+  let hue = (index as f64 * 0.618_033_988_75).fract() * 6.0;
+  let value = if infinite { 0.55 } else { 0.95 };
+  let saturation = 0.6;
+
+  let chroma = value * saturation;
+  let second = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+  let (r, g, b) = match hue as usize {
+    0 => (chroma, second, 0.0),
+    1 => (second, chroma, 0.0),
+    2 => (0.0, chroma, second),
+    3 => (0.0, second, chroma),
+    4 => (second, 0.0, chroma),
+    _ => (chroma, 0.0, second)
+  };
+
+  let base = value - chroma;
+  [((r + base) * 255.0) as u8, ((g + base) * 255.0) as u8, ((b + base) * 255.0) as u8]
+}
 
-    // println!();
+/// Lightens a colour halfway to white, to show which cells are in the safe region.
+fn highlight(colour: Rgb) -> Rgb {
+  [colour[0] / 2 + 128, colour[1] / 2 + 128, colour[2] / 2 + 128]
+}
+
+/// Draws a partition as an image with `scale` pixels for each side of a cell. Every region gets its
+/// own colour, darker if it's infinite, ties are dark grey and the coordinates themselves are
+/// black. Cells in the safe region, if there is one, are lightened.
+pub fn render_image(coords: &[Coord], picture: &Picture, scale: usize) -> Image {
+  let partition = &picture.partition;
+  let bounds = partition.extent();
+  let mut image = Image::new(bounds.len(0) * scale, bounds.len(1) * scale, TIE_COLOUR);
+
+  let pixel = |x: i64, y: i64| ((x - bounds.min[0]) as usize * scale, (y - bounds.min[1]) as usize * scale);
+
+  for ([x, y], owner) in partition.iter() {
+    let mut colour = match owner {
+      Owner::Coord(index) => region_colour(index, picture.infinite.binary_search(&index).is_ok()),
+      Owner::Tie => TIE_COLOUR
+    };
+
+    if picture.region.as_ref().is_some_and(|region| region.contains(x, y)) {
+      colour = highlight(colour);
+    }
+
+    let (px, py) = pixel(x, y);
+    image.fill_rect(px, py, scale, scale, colour);
   }
 
-  region_size
+  for coord in coords {
+    if bounds.contains(&coord.position()) {
+      let (px, py) = pixel(coord.x, coord.y);
+      image.fill_rect(px, py, scale, scale, SEED_COLOUR);
+    }
+  }
+
+  image
+}
+
+/// The distances along one axis from a position to every coordinate, added up.
+struct AxisSums {
+  positions: Vec<i64>,
+  /// `prefix[i]` is the sum of the first `i` positions.
+  prefix: Vec<i64>
+}
+
+impl AxisSums {
+  fn new(mut positions: Vec<i64>) -> AxisSums {
+    positions.sort();
+
+    let mut prefix = vec![0];
+    for position in positions.iter() {
+      prefix.push(prefix[prefix.len() - 1] + position);
+    }
+
+    AxisSums { positions, prefix }
+  }
+
+  fn total(&self, at: i64) -> i64 {
+    let n = self.positions.len();
+    let below = self.positions.partition_point(|position| *position <= at);
+
+    (at * below as i64 - self.prefix[below]) + (self.prefix[n] - self.prefix[below] - at * (n - below) as i64)
+  }
+
+  /// The smallest total, which is at the median.
+  fn min_total(&self) -> i64 {
+    self.total(self.positions[self.positions.len() / 2])
+  }
+
+  /// The totals at every position where the total is under `limit`. The total only goes down and
+  /// then up again, so these positions are all next to each other around the median, though
+  /// they can reach well past the outermost coordinates.
+  fn totals_under(&self, limit: i64) -> Vec<i64> {
+    let median = self.positions[self.positions.len() / 2];
+    let mut totals = Vec::new();
+
+    let mut at = median;
+    while self.total(at) < limit {
+      totals.push(self.total(at));
+      at -= 1;
+    }
+
+    let mut at = median + 1;
+    while self.total(at) < limit {
+      totals.push(self.total(at));
+      at += 1;
+    }
+
+    totals
+  }
+}
+
+/// How many cells have a total Manhattan distance to every coordinate under `threshold`, wherever
+/// they are. Manhattan distance is the distance along x plus the distance along y, so a cell's
+/// total is the total along x for its column plus the total along y for its row. Working those
+/// out for each column and row, and sorting the rows', lets each column count its cells with a
+/// binary search.
+///
+/// Without any coordinates every cell would count, so this returns 0 instead.
+pub fn safe_region_size(coords: &[Coord], threshold: i64) -> usize {
+  safe_volume(&positions(coords), threshold)
+}
+
+#[aoc(day6, part2)]
+pub fn part2(coords: &[Coord]) -> usize {
+  safe_region_size(coords, 10000)
+}
+
+/// Like `safe_region_size`, but for any metric, and only counting cells inside the smallest bounds
+/// holding every coordinate.
+pub fn part2_inner<M: Metric + ?Sized>(metric: &M, threshold: i64, coords: &[Coord]) -> usize {
+  bounds_for_coords(coords)
+    .map_or(0, |bounds| safe_region(coords, bounds, metric, threshold).len())
+}
+
+/// How many cells have a total Manhattan distance to every point under `threshold`, wherever they
+/// are, in any number of dimensions. The total splits into one total per axis like in
+/// `safe_region_size`: this tries every combination of positions along all but the last axis that
+/// could still be under the threshold, and counts the positions along the last axis with a binary
+/// search.
+///
+/// Without any points every cell would count, so this returns 0 instead.
+pub fn safe_volume<const N: usize>(points: &[[i64; N]], threshold: i64) -> usize {
+  if points.is_empty() || N == 0 {
+    return 0;
+  }
+
+  let sums = (0..N)
+    .map(|axis| AxisSums::new(points.iter().map(|point| point[axis]).collect()))
+    .collect::<Vec<AxisSums>>();
+  let min_total = sums.iter().map(|sums| sums.min_total()).sum::<i64>();
+
+  let totals = sums.iter()
+    .map(|sums| {
+      let mut totals = sums.totals_under(threshold - (min_total - sums.min_total()));
+      totals.sort();
+      totals
+    })
+    .collect::<Vec<Vec<i64>>>();
+
+  fn count(totals: &[Vec<i64>], budget: i64) -> usize {
+    match totals {
+      [last] => last.partition_point(|total| *total < budget),
+      [first, rest @ ..] => first.iter()
+        .take_while(|total| **total < budget)
+        .map(|total| count(rest, budget - total))
+        .sum(),
+      [] => 0
+    }
+  }
+
+  count(&totals, threshold)
 }
 
 #[cfg(test)]
 mod tests {
   use crate::day6::*;
+  use crate::testing::Lcg;
+
+  const EXAMPLE: &str = "1, 1\n1, 6\n8, 3\n3, 4\n5, 5\n8, 9";
 
   #[test]
   pub fn coordinate_parser_test() {
     assert_eq!([
-      Coord { x: 1, y: 1, name: 'A' },
-      Coord { x: 1, y: 6, name: 'B' },
-      Coord { x: 8, y: 3, name: 'C' },
-      Coord { x: 3, y: 4, name: 'D' },
-      Coord { x: 5, y: 5, name: 'E' },
-      Coord { x: 8, y: 9, name: 'F' },
-    ].to_vec(), coordinate_parser("1, 1\n1, 6\n8, 3\n3, 4\n5, 5\n8, 9"));
+      Coord { x: 1, y: 1, id: 0, label: Some('A') },
+      Coord { x: 1, y: 6, id: 1, label: Some('B') },
+      Coord { x: 8, y: 3, id: 2, label: Some('C') },
+      Coord { x: 3, y: 4, id: 3, label: Some('D') },
+      Coord { x: 5, y: 5, id: 4, label: Some('E') },
+      Coord { x: 8, y: 9, id: 5, label: Some('F') },
+    ].to_vec(), coordinate_parser(EXAMPLE).unwrap());
+  }
+
+  #[test]
+  pub fn coordinate_parser_many_test() {
+    let input = (0..100).map(|i| format!("{}, {}", i * 40000, -i)).collect::<Vec<String>>().join("\n");
+    let coords = coordinate_parser(&input).unwrap();
+
+    assert_eq!(100, coords.len());
+    assert_eq!((3960000, -99, 99), (coords[99].x(), coords[99].y(), coords[99].id()));
+    assert_eq!(Some('z'), coords[61].label());
+    assert_eq!(None, coords[62].label());
+    assert_eq!(("z".to_string(), "#62".to_string()), (coords[61].display_name(), coords[62].display_name()));
+  }
+
+  #[test]
+  pub fn coordinate_parser_error_test() {
+    let error = coordinate_parser("1, 1\n1 6").unwrap_err();
+    assert_eq!((2, 1, "1 6"), (error.line, error.column, error.text.as_str()));
+
+    let error = coordinate_parser("1, 1\n1, six").unwrap_err();
+    assert_eq!((2, 4, "six"), (error.line, error.column, error.text.as_str()));
+  }
+
+  #[test]
+  pub fn nearest_coords_test() {
+    let coords = coordinate_parser(EXAMPLE).unwrap();
+    let bounds = bounds_for_coords(&coords).unwrap();
+    let partition = nearest_coords(&coords, bounds, &Manhattan);
+
+    assert_eq!(nearest_coords_brute_force(&coords, bounds, &Manhattan), partition);
+    assert_eq!(Owner::Coord(0), partition.owner(&[1, 1]));
+    assert_eq!(Owner::Tie, partition.owner(&[5, 1]));
+    assert_eq!(Owner::Coord(4), partition.owner(&[5, 2]));
+  }
+
+  #[test]
+  pub fn nearest_coords_matches_brute_force_test() {
+    let inputs = [
+      "0, 0\n4, 0",
+      "2, 2\n2, 2\n5, 7",
+      "3, 1\n1, 3\n7, 5\n5, 7\n4, 4",
+      "10, 3\n2, 17\n6, 6\n15, 15\n0, 9\n12, 0\n7, 11",
+    ];
+
+    for input in inputs.iter() {
+      let coords = coordinate_parser(input).unwrap();
+      let bounds = bounds_for_coords(&coords).unwrap();
+
+      for metric in [&Manhattan as &dyn Metric, &Chebyshev, &EuclideanSquared, &Weighted { x: 2, y: 3 }].iter() {
+        assert_eq!(
+          nearest_coords_brute_force(&coords, bounds, *metric),
+          nearest_coords(&coords, bounds, *metric),
+          "{} {:?}", input, metric.steps()
+        );
+      }
+    }
+  }
+
+  #[test]
+  pub fn bounds_for_coords_test() {
+    let coords = coordinate_parser(EXAMPLE).unwrap();
+    assert_eq!(Some(Bounds { min: [1, 1], max: [8, 9] }), bounds_for_coords(&coords));
+
+    let coords = coordinate_parser("-5, 12\n-3, 20").unwrap();
+    assert_eq!(Some(Bounds { min: [-5, 12], max: [-3, 20] }), bounds_for_coords(&coords));
+
+    assert_eq!(None, bounds_for_coords(&[]));
+  }
+
+  #[test]
+  pub fn convex_hull_test() {
+    let coords = coordinate_parser(EXAMPLE).unwrap();
+    assert_eq!(vec![0, 2, 5, 1], convex_hull(&positions(&coords)));
+
+    let coords = coordinate_parser("0, 0\n2, 0\n4, 0\n4, 4\n0, 0\n2, 2").unwrap();
+    assert_eq!(vec![0, 2, 3], convex_hull(&positions(&coords)));
+
+    let coords = coordinate_parser("0, 0\n3, 3\n1, 1").unwrap();
+    assert_eq!(vec![0, 1], convex_hull(&positions(&coords)));
+  }
+
+  #[test]
+  pub fn diagnose_test() {
+    let coords = coordinate_parser(EXAMPLE).unwrap();
+    let diagnostics = diagnose(&Manhattan, &coords).unwrap();
+
+    assert_eq!(vec![0, 1, 2, 5], diagnostics.infinite);
+    assert_eq!(9, diagnostics.volumes[3]);
+    assert_eq!(72, diagnostics.volumes.iter().sum::<usize>() + diagnostics.ties);
+    assert_eq!(Some((4, 17)), diagnostics.largest_finite());
+
+    assert_eq!(None, diagnose(&Manhattan, &[]));
+  }
+
+  #[test]
+  pub fn infinite_regions_test() {
+    // The coordinate at 9, 0 is inside the hull, but nearest to every cell far enough to the right
+    // under Manhattan distance.
+    let coords = coordinate_parser("0, 5\n0, -5\n10, 6\n10, -6\n9, 0").unwrap();
+    assert_eq!(vec![0, 1, 2, 3, 4], diagnose(&Manhattan, &coords).unwrap().infinite);
+    assert_eq!(vec![0, 1, 2, 3], diagnose(&EuclideanSquared, &coords).unwrap().infinite);
+
+    // Coordinates on the hull's edges are infinite under straight-line distance, but coordinates
+    // sharing a position are never nearest to anything.
+    let coords = coordinate_parser("0, 0\n4, 0\n2, 0\n2, 5\n2, 2\n2, 2").unwrap();
+    assert_eq!(vec![0, 1, 2, 3], diagnose(&EuclideanSquared, &coords).unwrap().infinite);
+  }
+
+  #[test]
+  pub fn infinite_regions_match_wide_partition_test() {
+    let inputs = [
+      EXAMPLE,
+      "3, 1\n1, 3\n7, 5\n5, 7\n4, 4",
+      "10, 3\n2, 17\n6, 6\n15, 15\n0, 9\n12, 0\n7, 11",
+      "0, 5\n0, -5\n10, 6\n10, -6\n9, 0",
+    ];
+
+    for input in inputs.iter() {
+      let coords = coordinate_parser(input).unwrap();
+      let wide = bounds_for_coords(&coords).unwrap().widened(60);
+
+      for metric in [&Manhattan as &dyn Metric, &Chebyshev, &Weighted { x: 2, y: 3 }].iter() {
+        let mut reaching_edge = nearest_coords(&coords, wide, *metric).iter()
+          .filter(|(cell, _)| wide.on_edge(cell))
+          .filter_map(|(_, owner)| match owner { Owner::Coord(index) => Some(index), Owner::Tie => None })
+          .collect::<Vec<usize>>();
+        reaching_edge.sort();
+        reaching_edge.dedup();
+
+        assert_eq!(reaching_edge, diagnose(*metric, &coords).unwrap().infinite, "{} {:?}", input, metric.steps());
+      }
+    }
+  }
+
+  #[test]
+  pub fn region_sizes_match_wide_partition_test() {
+    let inputs = [
+      EXAMPLE,
+      "3, 1\n1, 3\n7, 5\n5, 7\n4, 4",
+      "10, 3\n2, 17\n6, 6\n15, 15\n0, 9\n12, 0\n7, 11",
+      "1, 1\n4, 3\n10, 4\n10, 7\n4, 5\n14, 2\n7, 4",
+    ];
+
+    for input in inputs.iter() {
+      let coords = coordinate_parser(input).unwrap();
+      let wide = bounds_for_coords(&coords).unwrap().widened(150);
+
+      for metric in [&Manhattan as &dyn Metric, &Chebyshev, &EuclideanSquared, &Weighted { x: 2, y: 3 }].iter() {
+        let diagnostics = diagnose(*metric, &coords).unwrap();
+
+        let mut sizes = vec![0; coords.len()];
+        for (_, owner) in nearest_coords_brute_force(&coords, wide, *metric).iter() {
+          if let Owner::Coord(index) = owner {
+            sizes[index] += 1;
+          }
+        }
+
+        for (index, size) in sizes.iter().enumerate() {
+          if diagnostics.infinite.binary_search(&index).is_err() {
+            assert_eq!(*size, diagnostics.volumes[index], "{} {} {:?}", input, index, metric.steps());
+          }
+        }
+      }
+    }
+
+    let coords = coordinate_parser("1, 1\n4, 3\n10, 4\n10, 7\n4, 5\n14, 2\n7, 4").unwrap();
+    assert_eq!(17, diagnose(&Chebyshev, &coords).unwrap().volumes[1]);
+
+    // this region reaches far below the coordinates.
+    let coords = coordinate_parser("0, 0\n100, 0\n100, 100\n0, 100\n50, 1").unwrap();
+    let diagnostics = diagnose(&EuclideanSquared, &coords).unwrap();
+    assert_eq!(vec![0, 1, 2, 3], diagnostics.infinite);
+    assert_eq!(Some((4, 34125)), diagnostics.largest_finite());
+  }
+
+  #[test]
+  pub fn many_coords_test() {
+    let mut rng = Lcg::new(12345);
+    let mut next = |modulus: u64| rng.below(modulus) as i64;
+
+    let coords = (0..20000).map(|id| Coord::new(id, next(400), next(400))).collect::<Vec<Coord>>();
+    let diagnostics = diagnose(&Manhattan, &coords).unwrap();
+    let bounds = diagnostics.extent;
+
+    assert_eq!(bounds.volume(), diagnostics.volumes.iter().sum::<usize>() + diagnostics.ties);
+
+    let partition = nearest_coords(&coords, bounds, &Manhattan);
+    for _ in 0..50 {
+      let cell = [bounds.min[0] + next(400).min(bounds.max[0] - bounds.min[0]), bounds.min[1] + next(400).min(bounds.max[1] - bounds.min[1])];
+      let best = coords.iter().map(|coord| distance_between(&Manhattan, &cell, &coord.position())).min().unwrap();
+      let nearest = coords.iter().enumerate()
+        .filter(|(_, coord)| distance_between(&Manhattan, &cell, &coord.position()) == best)
+        .map(|(index, _)| index)
+        .collect::<Vec<usize>>();
+
+      let expected = if nearest.len() == 1 { Owner::Coord(nearest[0]) } else { Owner::Tie };
+      assert_eq!(expected, partition.owner(&cell), "{:?}", cell);
+    }
+
+    let text = render_text(&coords[..70], &picture(&Manhattan, &coords[..70], None).unwrap());
+    assert!(text.contains('*'));
+    assert!(text.contains("#6"));
+  }
+
+  #[test]
+  pub fn metric_test() {
+    assert_eq!(7, Manhattan.distance([3, -4]));
+    assert_eq!(4, Chebyshev.distance([3, -4]));
+    assert_eq!(25, EuclideanSquared.distance([3, -4]));
+    assert_eq!(18, Weighted { x: 2, y: 3 }.distance([3, -4]));
+    assert_eq!(9, Manhattan.distance([3, -4, 2]));
+    assert_eq!(5, Chebyshev.distance([3, -4, 5, 0]));
+    assert_eq!(6, Metric::<3>::steps(&Manhattan).unwrap().len());
+    assert_eq!(26, Metric::<3>::steps(&Chebyshev).unwrap().len());
   }
 
   #[test]
   pub fn part1_test() {
-    assert_eq!(17, part1(&coordinate_parser("1, 1\n1, 6\n8, 3\n3, 4\n5, 5\n8, 9")))
+    assert_eq!(17, part1(&coordinate_parser(EXAMPLE).unwrap()))
+  }
+
+  #[test]
+  pub fn safe_region_test() {
+    let coords = coordinate_parser(EXAMPLE).unwrap();
+    let region = safe_region(&coords, bounds_for_coords(&coords).unwrap(), &Manhattan, 32);
+
+    assert_eq!(16, region.len());
+    assert!(region.contains(4, 3));
+    assert!(!region.contains(1, 1));
+    assert_eq!(Some(Bounds { min: [2, 3], max: [6, 6] }), region.bounding_box());
+    assert!(region.is_connected());
+    assert!(region.warnings.is_empty());
+  }
+
+  #[test]
+  pub fn safe_region_warnings_test() {
+    let coords = coordinate_parser(EXAMPLE).unwrap();
+    let bounds = bounds_for_coords(&coords).unwrap();
+
+    let region = safe_region(&coords, bounds, &Manhattan, 1000);
+    assert_eq!(region.search_bounds, region.bounding_box().unwrap());
+    assert_eq!(vec![RegionWarning::TouchesSearchBounds { cells: 30 }], region.warnings);
+
+    let region = safe_region(&coords, bounds, &Manhattan, 0);
+    assert!(region.is_empty());
+    assert_eq!(None, region.bounding_box());
+    assert!(region.is_connected());
+  }
+
+  #[test]
+  pub fn safe_region_connectivity_test() {
+    let search_bounds = Bounds { min: [0, 0], max: [3, 3] };
+    let region = |cells: &[(i64, i64)]| SafeRegion { search_bounds, cells: cells.iter().cloned().collect(), warnings: Vec::new() };
+
+    assert!(region(&[(0, 0), (1, 0), (1, 1), (1, 2)]).is_connected());
+    assert!(!region(&[(0, 0), (1, 1)]).is_connected());
+    assert!(!region(&[(0, 0), (0, 1), (2, 2)]).is_connected());
   }
-}
\ No newline at end of file
+
+  #[test]
+  pub fn render_text_test() {
+    let coords = coordinate_parser(EXAMPLE).unwrap();
+    let picture = picture(&Manhattan, &coords, Some(32)).unwrap();
+
+    assert_eq!("Aaaa.ccc
+aaddeccc
+ad###ccC
+.#D###cc
+b###E#ec
+Bb###ee.
+bb.eeeff
+bb.eefff
+bb.ffffF
+infinite: A, B, C, F
+", render_text(&coords, &picture));
+  }
+
+  #[test]
+  pub fn render_text_glyphs_test() {
+    let coords = coordinate_parser("0, 0\n4, 0\n0, 4\n4, 4").unwrap();
+    let coords = [
+      coords[0],
+      coords[1].with_label(Some('1')),
+      coords[2].with_label(Some('a')),
+      coords[3].with_label(None)
+    ];
+
+    assert_eq!(vec!['*', '*', '*', '*'], cell_glyphs(&coords));
+    assert_eq!(vec!['*', '*', '*', 'd'], cell_glyphs(&[coords[0], coords[1], coords[2], coords[3].with_label(Some('D'))]));
+    assert_eq!(vec!['a', 'b'], cell_glyphs(&coordinate_parser("0, 0\n4, 0").unwrap()));
+
+    let picture = picture(&Manhattan, &coords, None).unwrap();
+    assert_eq!("A*.*1
+**.**
+.....
+**.**
+a*.**
+infinite: A, 1, a, #3
+", render_text(&coords, &picture));
+  }
+
+  #[test]
+  pub fn render_image_test() {
+    let coords = coordinate_parser(EXAMPLE).unwrap();
+    let picture = picture(&Manhattan, &coords, Some(32)).unwrap();
+    let image = render_image(&coords, &picture, 3);
+
+    assert_eq!((24, 27), (image.width(), image.height()));
+    assert_eq!(SEED_COLOUR, image.get(1, 2));
+    assert_eq!(region_colour(0, true), image.get(3, 0));
+    assert_eq!(TIE_COLOUR, image.get(3 * 4, 0));
+    assert_eq!(region_colour(3, false), image.get(3 * 2, 3));
+    assert_eq!(highlight(region_colour(3, false)), image.get(3, 3 * 3));
+    assert_ne!(region_colour(3, false), region_colour(4, false));
+  }
+
+  #[test]
+  pub fn part2_test() {
+    let coords = coordinate_parser(EXAMPLE).unwrap();
+
+    assert_eq!(16, part2_inner(&Manhattan, 32, &coords));
+    assert_eq!(16, safe_region_size(&coords, 32));
+  }
+
+  #[test]
+  pub fn safe_region_size_test() {
+    let inputs = [
+      EXAMPLE,
+      "0, 0",
+      "2, 2\n2, 2\n5, 7",
+      "10, 3\n2, 17\n6, 6\n15, 15\n0, 9\n12, 0\n7, 11",
+      "-4, 9\n-4, -9\n3, 0",
+    ];
+
+    for input in inputs.iter() {
+      let coords = coordinate_parser(input).unwrap();
+      let bounds = bounds_for_coords(&coords).unwrap();
+
+      for threshold in [0, 1, 5, 32, 60, 100, 200].iter() {
+        // cells on the edge of these bounds are at least the threshold away from every coordinate
+        let wide = bounds.widened(*threshold);
+        let region = safe_region(&coords, wide, &Manhattan, *threshold);
+
+        assert!(region.warnings.is_empty(), "{} {}", input, threshold);
+        assert_eq!(region.len(), safe_region_size(&coords, *threshold), "{} {}", input, threshold);
+      }
+    }
+
+    assert_eq!(0, safe_region_size(&[], 100));
+  }
+
+  fn manhattan<const N: usize>(a: &[i64; N], b: &[i64; N]) -> i64 {
+    a.iter().zip(b.iter()).map(|(a, b)| (a - b).abs()).sum()
+  }
+
+  fn random_points<const N: usize>(seed: u64, count: usize, size: u64) -> Vec<[i64; N]> {
+    let mut rng = Lcg::new(seed);
+    (0..count)
+      .map(|_| {
+        let mut point = [0; N];
+        for position in point.iter_mut() {
+          *position = rng.below(size) as i64;
+        }
+        point
+      })
+      .collect()
+  }
+
+  #[test]
+  pub fn region_volumes_on_a_plane_test() {
+    // coordinates all on one plane in three dimensions split it up just like in two, except that
+    // every region carries on forever along the third axis.
+    let coords = coordinate_parser(EXAMPLE).unwrap();
+    let points = coords.iter().map(|coord| [coord.x(), coord.y(), 0]).collect::<Vec<[i64; 3]>>();
+
+    let diagnostics = diagnose(&Manhattan, &coords).unwrap();
+    let volumes = region_volumes(&Manhattan, &points).unwrap();
+
+    assert_eq!(diagnostics.volumes, volumes.volumes);
+    assert_eq!(diagnostics.ties, volumes.ties);
+    assert_eq!((0..6).collect::<Vec<usize>>(), volumes.infinite);
+
+    let partition = nearest_coords(&coords, diagnostics.extent, &Manhattan);
+    for (point, owner) in nearest_points(&points, volumes.extent, &Manhattan).iter() {
+      assert_eq!(partition.owner(&[point[0], point[1]]), owner);
+    }
+
+    assert_eq!(None, region_volumes(&Manhattan, &[] as &[[i64; 3]]));
+    assert_eq!(None, region_volumes(&EuclideanSquared, &points));
+  }
+
+  #[test]
+  pub fn region_volumes_3d_test() {
+    let mut points = Vec::new();
+    for corner in 0..8 {
+      points.push([(corner & 1) * 4, (corner >> 1 & 1) * 4, (corner >> 2 & 1) * 4]);
+    }
+    points.push([2, 2, 2]);
+
+    // Straight out from the middle of each face, the middle of the cube is always nearer than
+    // the corners, so every region is infinite.
+    let volumes = region_volumes(&Manhattan, &points).unwrap();
+    assert_eq!(125, volumes.volumes.iter().sum::<usize>() + volumes.ties);
+    assert_eq!((0..9).collect::<Vec<usize>>(), volumes.infinite);
+    assert_eq!(None, volumes.largest_finite());
+
+    // A point just past the middle of each face cuts it off.
+    points.extend_from_slice(&[[-1, 2, 2], [5, 2, 2], [2, -1, 2], [2, 5, 2], [2, 2, -1], [2, 2, 5]]);
+    let volumes = region_volumes(&Manhattan, &points).unwrap();
+    assert_eq!((0..8).chain(9..15).collect::<Vec<usize>>(), volumes.infinite);
+    assert_eq!(8, volumes.largest_finite().unwrap().0);
+  }
+
+  fn check_region_volumes<M: Metric<N>, const N: usize>(metric: &M, points: &[[i64; N]]) {
+    let volumes = region_volumes(metric, points).unwrap();
+
+    assert_eq!(
+      nearest_points_brute_force(points, volumes.searched, metric),
+      nearest_points(points, volumes.searched, metric),
+      "{:?}", points
+    );
+
+    let wide = volumes.searched.widened(6);
+    let lattice = nearest_points(points, wide, metric);
+
+    let mut reaching_edge = lattice.iter()
+      .filter(|(cell, _)| wide.on_edge(cell))
+      .filter_map(|(_, owner)| match owner { Owner::Coord(index) => Some(index), Owner::Tie => None })
+      .collect::<Vec<usize>>();
+    reaching_edge.sort();
+    reaching_edge.dedup();
+
+    assert_eq!(reaching_edge, volumes.infinite, "{:?}", points);
+
+    for index in 0..points.len() {
+      if volumes.infinite.binary_search(&index).is_err() {
+        let volume = lattice.iter().filter(|(_, owner)| *owner == Owner::Coord(index)).count();
+        assert_eq!(volume, volumes.volumes[index], "{:?} {}", points, index);
+      }
+    }
+  }
+
+  #[test]
+  pub fn region_volumes_match_brute_force_test() {
+    check_region_volumes(&Manhattan, &random_points::<3>(1, 12, 8));
+    check_region_volumes(&Manhattan, &random_points::<3>(2, 30, 6));
+    check_region_volumes(&Manhattan, &random_points::<4>(3, 20, 5));
+    check_region_volumes(&Chebyshev, &random_points::<3>(8, 12, 6));
+    check_region_volumes(&Manhattan, &random_points::<2>(10, 15, 20));
+    check_region_volumes(&Chebyshev, &random_points::<2>(11, 15, 20));
+    check_region_volumes(&EuclideanSquared, &random_points::<2>(12, 15, 20));
+  }
+
+  #[test]
+  pub fn region_volumes_thin_region_test() {
+    // point 3's region is a thin sliver, and its lattice cells stop and start again on the way
+    // out, so not reaching the edge of a lattice doesn't mean it's all inside
+    let points = [[3, 29], [16, 2], [23, 27], [17, 8]];
+    let volumes = region_volumes(&EuclideanSquared, &points).unwrap();
+
+    assert_eq!(vec![0, 1, 2], volumes.infinite);
+    assert_eq!(802, volumes.volumes[3]);
+    assert!(volumes.searched.contains(&[107, -10]));
+
+    let wide = Bounds { min: [-100, -300], max: [300, 100] };
+    let lattice = nearest_points_brute_force(&points, wide, &EuclideanSquared);
+    assert_eq!(802, lattice.iter().filter(|(_, owner)| *owner == Owner::Coord(3)).count());
+
+    check_region_volumes(&EuclideanSquared, &points);
+  }
+
+  fn check_safe_volume<const N: usize>(points: &[[i64; N]], threshold: i64) {
+    let wide = Extent::around(points).unwrap().widened(threshold);
+    let lattice = Lattice { extent: wide, cells: vec![Owner::Tie; wide.volume()] };
+
+    let expected = lattice.iter()
+      .filter(|(cell, _)| points.iter().map(|point| manhattan(point, cell)).sum::<i64>() < threshold)
+      .count();
+
+    assert_eq!(expected, safe_volume(points, threshold), "{:?} {}", points, threshold);
+  }
+
+  #[test]
+  pub fn safe_volume_test() {
+    check_safe_volume(&[[0, 0, 0]], 4);
+    check_safe_volume(&random_points::<3>(4, 6, 5), 30);
+    check_safe_volume(&random_points::<3>(5, 9, 7), 32);
+    check_safe_volume(&random_points::<4>(6, 5, 4), 10);
+    check_safe_volume(&random_points::<1>(7, 4, 10), 40);
+
+    // a single point's safe region is an octahedron
+    assert_eq!(25, safe_volume(&[[0, 0, 0]], 3));
+    assert_eq!(0, safe_volume::<3>(&[], 10));
+  }
+}
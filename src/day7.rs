@@ -1,8 +1,8 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 use regex::Regex;
 use std::collections::HashMap;
-use std::cmp::Ordering;
 use std::collections::HashSet;
+use crate::parse::{ParseError, parse_match};
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub struct Rule {
@@ -11,22 +11,23 @@ pub struct Rule {
 }
 
 #[aoc_generator(day7)]
-pub fn rule_parser(input: &str) -> Vec<Rule> {
+pub fn rule_parser(input: &str) -> Result<Vec<Rule>, ParseError> {
   let mut rules = Vec::new();
   lazy_static! {
-      static ref RULE_RE: Regex  = Regex::new(r"Step (.) must be finished before step (.) can begin.$").unwrap();
+      static ref RULE_RE: Regex  = Regex::new(r"^Step (.) must be finished before step (.) can begin.$").unwrap();
   }
 
-  for line in input.lines() {
-    for cap in RULE_RE.captures_iter(line) {
-      rules.push(Rule {
-        step: cap[2].parse::<char>().unwrap(),
-        prerequisite: cap[1].parse::<char>().unwrap()
-      });
-    }
+  for (index, line) in input.lines().enumerate() {
+    let cap = RULE_RE.captures(line)
+      .ok_or_else(|| ParseError::line(index, line, "a rule like \"Step <X> must be finished before step <Y> can begin.\""))?;
+
+    rules.push(Rule {
+      step: parse_match::<char>(index, line, cap.get(2).unwrap(), "a step name")?,
+      prerequisite: parse_match::<char>(index, line, cap.get(1).unwrap(), "a step name")?
+    });
   }
 
-  rules
+  Ok(rules)
 }
 
 #[aoc(day7, part1)]
@@ -109,7 +110,6 @@ pub fn part2_inner(num_elves: usize, base_step_duration: usize, rules: &Vec<Rule
   }
 
   let mut closed: HashSet<char> = HashSet::new();
-  let mut sequence: Vec<char> = Vec::new();
 
   let mut second = 0;
   loop {
@@ -190,7 +190,14 @@ Step A must be finished before step B can begin.
 Step A must be finished before step D can begin.
 Step B must be finished before step E can begin.
 Step D must be finished before step E can begin.
-Step F must be finished before step E can begin."));
+Step F must be finished before step E can begin.").unwrap());
+  }
+
+  #[test]
+  pub fn rule_parser_error_test() {
+    let error = rule_parser("Step C must be finished before step A can begin.
+Step CA must be finished before step F can begin.").unwrap_err();
+    assert_eq!((2, 1), (error.line, error.column));
   }
 
   #[test]
@@ -201,7 +208,7 @@ Step A must be finished before step B can begin.
 Step A must be finished before step D can begin.
 Step B must be finished before step E can begin.
 Step D must be finished before step E can begin.
-Step F must be finished before step E can begin.")));
+Step F must be finished before step E can begin.").unwrap()));
   }
 
   #[test]
@@ -212,7 +219,7 @@ Step A must be finished before step B can begin.
 Step A must be finished before step D can begin.
 Step B must be finished before step E can begin.
 Step D must be finished before step E can begin.
-Step F must be finished before step E can begin.")));
+Step F must be finished before step E can begin.").unwrap()));
   }
 
 }
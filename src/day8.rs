@@ -1,4 +1,5 @@
 use aoc_runner_derive::{aoc, aoc_generator};
+use crate::parse::ParseError;
 
 #[derive(Debug, Eq, PartialEq)]
 pub struct Node(char, Vec<usize>, Vec<Node>);
@@ -13,40 +14,81 @@ pub struct Licence {
   root: Node
 }
 
-fn parse_node<A: Iterator<Item=usize>, B: Iterator<Item=char>>(numbers: &mut A, names: &mut B) -> Node {
-  let num_children = numbers.next().unwrap();
-  let num_metadata = numbers.next().unwrap();
+/// A number from the input along with where it was found, for error reporting.
+struct Token<'a> {
+  line_index: usize,
+  line: &'a str,
+  offset: usize,
+  text: &'a str
+}
+
+fn tokenize(input: &str) -> Vec<Token<'_>> {
+  let mut tokens = Vec::new();
+
+  for (line_index, line) in input.lines().enumerate() {
+    let mut rest = line;
+
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+      let end = rest[start..].find(char::is_whitespace).map(|e| start + e).unwrap_or(rest.len());
+
+      tokens.push(Token {
+        line_index,
+        line,
+        offset: line.len() - rest.len() + start,
+        text: &rest[start..end]
+      });
+
+      rest = &rest[end..];
+    }
+  }
+
+  tokens
+}
+
+fn next_number<'a, A: Iterator<Item=&'a Token<'a>>>(input: &str, tokens: &mut A, expected: &str) -> Result<usize, ParseError> {
+  match tokens.next() {
+    Some(token) => token.text.parse()
+      .map_err(|_| ParseError::new(token.line_index, token.line, token.offset, token.text, expected)),
+    None => Err(ParseError::end_of_input(input, expected))
+  }
+}
+
+fn parse_node<'a, A: Iterator<Item=&'a Token<'a>>, B: Iterator<Item=char>>(input: &str, tokens: &mut A, names: &mut B) -> Result<Node, ParseError> {
+  let num_children = next_number(input, tokens, "a child count")?;
+  let num_metadata = next_number(input, tokens, "a metadata count")?;
 
   let name = names.next().expect("Ran out of names!");
   let mut children = Vec::new();
   let mut metadata = Vec::new();
 
   for _ in 0..num_children {
-    children.push(parse_node(numbers, names));
+    children.push(parse_node(input, tokens, names)?);
   }
 
   for _ in 0..num_metadata {
-    metadata.push(numbers.next().expect("No more numbers for metadata"));
+    metadata.push(next_number(input, tokens, "a metadata entry")?);
   }
 
-  Node(name, metadata, children)
+  Ok(Node(name, metadata, children))
 }
 
-pub fn parse_input(input: &str) -> Licence {
-  let mut names = "ABCDEFGHIJKLMNOPQRSTUVWXYZ01234567890abcdefghijklmnopqrstuvwxyz".chars().into_iter().cycle();
-  let mut numbers = input.split_whitespace().map(|str| str.parse().unwrap());
+#[aoc_generator(day8)]
+pub fn parse_input(input: &str) -> Result<Licence, ParseError> {
+  let mut names = "ABCDEFGHIJKLMNOPQRSTUVWXYZ01234567890abcdefghijklmnopqrstuvwxyz".chars().cycle();
+  let tokens = tokenize(input);
+  let mut iter = tokens.iter();
+
+  let root = parse_node(input, &mut iter, &mut names)?;
 
-  let root = parse_node(&mut numbers, &mut names);
+  if let Some(token) = iter.next() {
+    return Err(ParseError::new(token.line_index, token.line, token.offset, token.text, "the end of the licence"));
+  }
 
-  Licence { root }
+  Ok(Licence { root })
 }
 
 #[aoc(day8, part1)]
-pub fn part1(input: &str) -> usize {
-  let licence = parse_input(input);
-
-  println!("Licence: {:?}", licence);
-
+pub fn part1(licence: &Licence) -> usize {
   fn sum_metadata(node: &Node) -> usize {
     let mut sum = 0;
 
@@ -65,8 +107,7 @@ pub fn part1(input: &str) -> usize {
 }
 
 #[aoc(day8, part2)]
-pub fn part2(input: &str) -> usize {
-  let licence = parse_input(input);
+pub fn part2(licence: &Licence) -> usize {
 
   fn node_value(node: Option<&Node>) -> usize {
     let mut value = 0;
@@ -102,11 +143,23 @@ mod tests {
           Node('D', vec![99], vec![])
         ])
       ])
-    }, parse_input("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2"));
+    }, parse_input("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2").unwrap());
+  }
+
+  #[test]
+  pub fn licence_parser_error_test() {
+    let error = parse_input("2 3 0 3 10 11 12\n1 1 0 1 x 2 1 1 2").unwrap_err();
+    assert_eq!((2, 9, "x"), (error.line, error.column, error.text.as_str()));
+
+    let error = parse_input("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1").unwrap_err();
+    assert_eq!((1, 34, "a metadata entry"), (error.line, error.column, error.expected.as_str()));
+
+    let error = parse_input("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2 7").unwrap_err();
+    assert_eq!((1, 37, "7"), (error.line, error.column, error.text.as_str()));
   }
 
   #[test]
   pub fn part1_test() {
-    assert_eq!(138, part1("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2"));
+    assert_eq!(138, part1(&parse_input("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2").unwrap()));
   }
 }
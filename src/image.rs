@@ -0,0 +1,202 @@
+/// A colour as red, green and blue bytes.
+pub type Rgb = [u8; 3];
+
+/// A plain RGB image that can be saved as PPM or PNG without pulling in an image library.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Image {
+  width: usize,
+  height: usize,
+  pixels: Vec<Rgb>
+}
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+// The most a stored deflate block can hold.
+const STORED_BLOCK_SIZE: usize = 65535;
+
+impl Image {
+  pub fn new(width: usize, height: usize, background: Rgb) -> Image {
+    Image { width, height, pixels: vec![background; width * height] }
+  }
+
+  pub fn width(&self) -> usize {
+    self.width
+  }
+
+  pub fn height(&self) -> usize {
+    self.height
+  }
+
+  pub fn get(&self, x: usize, y: usize) -> Rgb {
+    self.pixels[y * self.width + x]
+  }
+
+  pub fn set(&mut self, x: usize, y: usize, colour: Rgb) {
+    self.pixels[y * self.width + x] = colour;
+  }
+
+  /// Colours a `width` by `height` rectangle with its top left corner at `x`, `y`, clipped to the
+  /// edges of the image.
+  pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, colour: Rgb) {
+    for y in y..(y + height).min(self.height) {
+      for x in x..(x + width).min(self.width) {
+        self.set(x, y, colour);
+      }
+    }
+  }
+
+  /// The image as a binary (P6) PPM file.
+  pub fn to_ppm(&self) -> Vec<u8> {
+    let mut out = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+
+    for pixel in self.pixels.iter() {
+      out.extend_from_slice(pixel);
+    }
+
+    out
+  }
+
+  /// The image as a PNG file. The pixel data isn't compressed, so the files are big, but any
+  /// viewer can open them.
+  pub fn to_png(&self) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend_from_slice(&(self.width as u32).to_be_bytes());
+    header.extend_from_slice(&(self.height as u32).to_be_bytes());
+    // 8 bits per channel, RGB, deflate compression, standard filtering, not interlaced
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut scanlines = Vec::with_capacity(self.height * (self.width * 3 + 1));
+    // every row starts with its filter type, even when it has no pixels
+    for y in 0..self.height {
+      scanlines.push(0);
+      for pixel in self.pixels[y * self.width..(y + 1) * self.width].iter() {
+        scanlines.extend_from_slice(pixel);
+      }
+    }
+
+    let mut out = PNG_SIGNATURE.to_vec();
+    write_chunk(&mut out, b"IHDR", &header);
+    write_chunk(&mut out, b"IDAT", &zlib_stored(&scanlines));
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+  }
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+  out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+  let start = out.len();
+  out.extend_from_slice(kind);
+  out.extend_from_slice(data);
+
+  let crc = crc32(&out[start..]);
+  out.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wraps `data` in a zlib stream made of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+  let mut out = vec![0x78, 0x01];
+  let mut blocks = data.chunks(STORED_BLOCK_SIZE).peekable();
+
+  if blocks.peek().is_none() {
+    out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+  }
+
+  while let Some(block) = blocks.next() {
+    let last = blocks.peek().is_none();
+    let length = block.len() as u16;
+
+    out.push(last as u8);
+    out.extend_from_slice(&length.to_le_bytes());
+    out.extend_from_slice(&(!length).to_le_bytes());
+    out.extend_from_slice(block);
+  }
+
+  out.extend_from_slice(&adler32(data).to_be_bytes());
+  out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+  let mut crc = !0u32;
+
+  for byte in data {
+    crc ^= *byte as u32;
+
+    for _ in 0..8 {
+      crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+    }
+  }
+
+  !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+  let (mut a, mut b) = (1u32, 0u32);
+
+  for byte in data {
+    a = (a + *byte as u32) % 65521;
+    b = (b + a) % 65521;
+  }
+
+  (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::image::*;
+
+  #[test]
+  pub fn checksum_test() {
+    assert_eq!(0xae42_6082, crc32(b"IEND"));
+    assert_eq!(0xcbf4_3926, crc32(b"123456789"));
+    assert_eq!(0x11e6_0398, adler32(b"Wikipedia"));
+  }
+
+  #[test]
+  pub fn zlib_stored_test() {
+    assert_eq!(vec![0x78, 0x01, 1, 3, 0, 0xfc, 0xff, b'a', b'b', b'c', 0x02, 0x4d, 0x01, 0x27], zlib_stored(b"abc"));
+    assert_eq!(vec![0x78, 0x01, 1, 0, 0, 0xff, 0xff, 0, 0, 0, 1], zlib_stored(b""));
+
+    let data = vec![7; STORED_BLOCK_SIZE + 10];
+    let stream = zlib_stored(&data);
+    assert_eq!(2 + 5 + STORED_BLOCK_SIZE + 5 + 10 + 4, stream.len());
+    assert_eq!(0, stream[2]);
+    assert_eq!(1, stream[2 + 5 + STORED_BLOCK_SIZE]);
+  }
+
+  #[test]
+  pub fn ppm_test() {
+    let mut image = Image::new(2, 1, [0, 0, 0]);
+    image.set(1, 0, [255, 128, 1]);
+
+    assert_eq!(b"P6\n2 1\n255\n\x00\x00\x00\xff\x80\x01".to_vec(), image.to_ppm());
+  }
+
+  #[test]
+  pub fn png_test() {
+    let mut image = Image::new(3, 2, [10, 20, 30]);
+    image.fill_rect(1, 1, 5, 5, [1, 2, 3]);
+
+    let png = image.to_png();
+    assert_eq!(PNG_SIGNATURE, png[..8]);
+    assert_eq!(b"\x00\x00\x00\x0dIHDR\x00\x00\x00\x03\x00\x00\x00\x02\x08\x02\x00\x00\x00", &png[8..29]);
+    assert_eq!(b"\x00\x00\x00\x00IEND\xae\x42\x60\x82", &png[png.len() - 12..]);
+
+    let scanlines = [0, 10, 20, 30, 10, 20, 30, 10, 20, 30, 0, 10, 20, 30, 1, 2, 3, 1, 2, 3];
+    let idat = zlib_stored(&scanlines);
+    assert_eq!(&(idat.len() as u32).to_be_bytes()[..], &png[33..37]);
+    assert_eq!(&idat[..], &png[41..41 + idat.len()]);
+  }
+
+  #[test]
+  pub fn empty_png_test() {
+    let png = Image::new(0, 3, [0, 0, 0]).to_png();
+    let idat = zlib_stored(&[0, 0, 0]);
+    assert_eq!(&(idat.len() as u32).to_be_bytes()[..], &png[33..37]);
+    assert_eq!(&idat[..], &png[41..41 + idat.len()]);
+
+    let png = Image::new(3, 0, [0, 0, 0]).to_png();
+    let idat = zlib_stored(&[]);
+    assert_eq!(&idat[..], &png[41..41 + idat.len()]);
+  }
+}
@@ -1,5 +1,3 @@
-#![feature(step_trait)]
-
 use aoc_runner_derive::aoc_lib;
 #[macro_use] extern crate lazy_static;
 
@@ -11,5 +9,8 @@ pub mod day5;
 pub mod day6;
 pub mod day7;
 pub mod day8;
+pub mod image;
+pub mod parse;
+#[cfg(test)] mod testing;
 
 aoc_lib!{ year = 2018 }
\ No newline at end of file
@@ -0,0 +1,97 @@
+use regex::Match;
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::str::FromStr;
+
+/// An error raised by one of the `aoc_generator` functions when a line of input doesn't have the
+/// shape the day expects. Lines and columns are 1-based and columns count characters, not bytes.
+#[derive(Clone, Eq, PartialEq)]
+pub struct ParseError {
+  pub line: usize,
+  pub column: usize,
+  pub text: String,
+  pub expected: String,
+  pub source_line: String
+}
+
+impl ParseError {
+  /// Builds an error pointing at `text`, which starts at byte offset `offset` of `source_line`.
+  /// `line_index` is the 0-based index you get from `input.lines().enumerate()`.
+  pub fn new(line_index: usize, source_line: &str, offset: usize, text: &str, expected: &str) -> ParseError {
+    ParseError {
+      line: line_index + 1,
+      column: source_line[..offset].chars().count() + 1,
+      text: text.to_string(),
+      expected: expected.to_string(),
+      source_line: source_line.to_string()
+    }
+  }
+
+  /// An error covering a whole line that didn't match the expected shape at all.
+  pub fn line(line_index: usize, source_line: &str, expected: &str) -> ParseError {
+    ParseError::new(line_index, source_line, 0, source_line, expected)
+  }
+
+  /// An error for input that stopped before we found what we were looking for.
+  pub fn end_of_input(input: &str, expected: &str) -> ParseError {
+    let line_index = input.lines().count().saturating_sub(1);
+    let source_line = input.lines().last().unwrap_or("");
+
+    ParseError::new(line_index, source_line, source_line.len(), "", expected)
+  }
+}
+
+/// Parses the text of a regex capture, reporting the capture's position if it doesn't parse.
+pub fn parse_match<T: FromStr>(line_index: usize, source_line: &str, m: Match, expected: &str) -> Result<T, ParseError> {
+  m.as_str().parse::<T>()
+    .map_err(|_| ParseError::new(line_index, source_line, m.start(), m.as_str(), expected))
+}
+
+impl Display for ParseError {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    let found = if self.text.is_empty() {
+      "nothing".to_string()
+    } else {
+      format!("{:?}", self.text)
+    };
+
+    writeln!(f, "line {}, column {}: expected {}, found {}", self.line, self.column, self.expected, found)?;
+    writeln!(f, "  {}", self.source_line)?;
+    write!(f, "  {}{}", " ".repeat(self.column - 1), "^".repeat(self.text.chars().count().max(1)))
+  }
+}
+
+// cargo-aoc prints generator errors with `{:#?}`, so make that readable too.
+impl Debug for ParseError {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    Display::fmt(self, f)
+  }
+}
+
+impl Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+  use crate::parse::*;
+
+  #[test]
+  pub fn display_test() {
+    let error = ParseError::new(2, "#1 @ 1,x3: 4x4", 7, "x3", "an unsigned integer");
+
+    assert_eq!(3, error.line);
+    assert_eq!(8, error.column);
+    assert_eq!(
+      "line 3, column 8: expected an unsigned integer, found \"x3\"\n  #1 @ 1,x3: 4x4\n         ^^",
+      error.to_string()
+    );
+  }
+
+  #[test]
+  pub fn end_of_input_test() {
+    let error = ParseError::end_of_input("1 2\n3 4", "a number");
+
+    assert_eq!(2, error.line);
+    assert_eq!(4, error.column);
+    assert_eq!("line 2, column 4: expected a number, found nothing\n  3 4\n     ^", error.to_string());
+  }
+}
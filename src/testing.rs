@@ -0,0 +1,14 @@
+/// A linear congruential generator, so tests can make up inputs without a random number crate.
+pub struct Lcg(u64);
+
+impl Lcg {
+  pub fn new(seed: u64) -> Lcg {
+    Lcg(seed)
+  }
+
+  /// The next number, somewhere below `modulus`.
+  pub fn below(&mut self, modulus: u64) -> u64 {
+    self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    (self.0 >> 33) % modulus
+  }
+}